@@ -1,63 +1,177 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
-
-use crate::callbacks::{ConnectionCallback, MessageCallback};
-use crate::{EventLoopThreadPool, ReactorSocket, TcpConnection};
-use log::{error, trace};
+use std::time::Duration;
+
+use crate::callbacks::{
+    ConnectionCallback, HighWaterMarkCallback, IdleCallback, MessageCallback, WriteCompleteCallback,
+};
+use crate::error::{Result, parse_addr};
+use crate::reactor_socket::AsSocket;
+use crate::{ReactorRemote, ReactorSocket, TcpConnection, TcpOptions};
+use log::{error, trace, warn};
 use mio::Interest;
 use mio::net::{TcpListener, TcpStream};
+use socket2::{Domain, Protocol, Socket, Type};
+
+pub const DEFAULT_LISTEN_BACKLOG: u32 = 1024;
 
-pub struct Acceptor {
+// H 为 io reactor 中的 socket 类型，默认只容纳 TcpConnection
+pub struct Acceptor<H = TcpConnection>
+where
+    H: AsSocket<TcpConnection>,
+{
     listener: TcpListener,
-    event_loop_thread_pool: EventLoopThreadPool<TcpConnection>,
+    io_reactors: Vec<ReactorRemote<H>>,
+    reactor_index: usize,
     connection_callback: ConnectionCallback,
     message_callback: MessageCallback,
+    write_complete_callback: Option<WriteCompleteCallback>,
+    high_water_mark_callback: Option<(HighWaterMarkCallback, usize)>,
+    max_input_buffer: Option<usize>,
+    idle_timeout: Option<Duration>,
+    idle_callback: Option<IdleCallback>,
+    tcp_options: TcpOptions,
     poll_token: Option<mio::Token>,
     is_established: Arc<AtomicBool>,
 }
 
-impl Acceptor {
+impl<H> Acceptor<H>
+where
+    H: AsSocket<TcpConnection> + 'static,
+{
     pub fn new(
         addr: String,
-        event_loop_thread_pool: EventLoopThreadPool<TcpConnection>,
+        io_reactors: Vec<ReactorRemote<H>>,
+        connection_callback: ConnectionCallback,
+        message_callback: MessageCallback,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(parse_addr(&addr)?)?;
+        Ok(Self::with_listener(
+            listener,
+            io_reactors,
+            connection_callback,
+            message_callback,
+        ))
+    }
+
+    // 新连接轮流分配给 io_reactors，其线程由调用者管理
+    pub fn with_listener(
+        listener: TcpListener,
+        io_reactors: Vec<ReactorRemote<H>>,
         connection_callback: ConnectionCallback,
         message_callback: MessageCallback,
     ) -> Self {
+        assert!(
+            !io_reactors.is_empty(),
+            "Acceptor needs at least one io reactor"
+        );
         Acceptor {
-            listener: TcpListener::bind(addr.parse().unwrap()).unwrap(),
-            event_loop_thread_pool,
+            listener,
+            io_reactors,
+            reactor_index: 0,
             connection_callback,
             message_callback,
+            write_complete_callback: None,
+            high_water_mark_callback: None,
+            max_input_buffer: None,
+            idle_timeout: None,
+            idle_callback: None,
+            tcp_options: TcpOptions::default(),
             poll_token: None,
             is_established: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn set_write_complete_callback(&mut self, callback: WriteCompleteCallback) {
+        self.write_complete_callback = Some(callback);
+    }
+
+    pub fn set_high_water_mark_callback(
+        &mut self,
+        callback: HighWaterMarkCallback,
+        high_water_mark: usize,
+    ) {
+        self.high_water_mark_callback = Some((callback, high_water_mark));
+    }
+
+    pub fn set_max_input_buffer(&mut self, max_input_buffer: usize) {
+        self.max_input_buffer = Some(max_input_buffer);
+    }
+
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = Some(idle_timeout);
+    }
+
+    pub fn set_idle_callback(&mut self, callback: IdleCallback) {
+        self.idle_callback = Some(callback);
+    }
+
+    // 应用到每个新接受的连接
+    pub fn set_tcp_options(&mut self, options: TcpOptions) {
+        self.tcp_options = options;
+    }
+
     pub fn on_new_connection(&mut self, stream: TcpStream) {
-        let reactor_index = self.event_loop_thread_pool.reactor_index();
-        let reactor = self.event_loop_thread_pool.get_next_reactor();
+        let reactor_index = self.reactor_index;
+        let reactor = &self.io_reactors[reactor_index];
+        self.reactor_index = (reactor_index + 1) % self.io_reactors.len();
         trace!(
-            "New connection [{}->{}] will send to reactor({})",
-            stream.local_addr().unwrap(),
-            stream.peer_addr().unwrap(),
+            "New connection [{:?}->{:?}] will send to reactor({})",
+            stream.local_addr(),
+            stream.peer_addr(),
             reactor_index
         );
-        let connection = TcpConnection::new(
+        if let Err(e) = self.tcp_options.apply(&stream) {
+            warn!("Failed to set tcp options on accepted connection: {}", e);
+        }
+        let mut connection = TcpConnection::new(
             stream,
             self.connection_callback.clone(),
             self.message_callback.clone(),
             Interest::READABLE,
             reactor.get_sender(),
         );
-        reactor.register(connection);
-        trace!(
-            "Connection registered with reactor: {}",
-            self.event_loop_thread_pool.reactor_index()
-        );
+        if let Some(callback) = &self.write_complete_callback {
+            connection.set_write_complete_callback(callback.clone());
+        }
+        if let Some((callback, high_water_mark)) = &self.high_water_mark_callback {
+            connection.set_high_water_mark_callback(callback.clone(), *high_water_mark);
+        }
+        if let Some(max_input_buffer) = self.max_input_buffer {
+            connection.set_max_input_buffer(max_input_buffer);
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            connection.set_idle_timeout(idle_timeout);
+        }
+        if let Some(callback) = &self.idle_callback {
+            connection.set_idle_callback(callback.clone());
+        }
+        reactor.register(H::from(connection));
+        trace!("Connection registered with reactor: {}", reactor_index);
+    }
+}
+
+impl Acceptor {
+    // 与 mio 的 TcpListener::bind 相同，但可以指定 listen backlog
+    pub fn bind(addr: SocketAddr, backlog: u32) -> std::io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_reuse_address(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(backlog.min(i32::MAX as u32) as i32)?;
+        socket.set_nonblocking(true)?;
+        Ok(TcpListener::from_std(socket.into()))
     }
 }
 
-impl ReactorSocket for Acceptor {
+impl<H> ReactorSocket for Acceptor<H>
+where
+    H: AsSocket<TcpConnection> + 'static,
+{
     type Socket = TcpListener;
 
     fn handle_event(&mut self, event: &mio::event::Event, _receive_time: std::time::Instant) {
@@ -0,0 +1,322 @@
+use std::{any::Any, net::SocketAddr, time::Instant};
+
+use mio::event::Source;
+
+use crate::{Acceptor, ReactorSocket, TcpConnection, UdpSocket, reactor_socket::AsSocket};
+
+// 用户自定义的 socket，需要以 dyn Source 的形式暴露底层 fd
+pub trait CustomSocket: ReactorSocket<Socket = dyn Source> + Any {}
+
+impl<T> CustomSocket for T where T: ReactorSocket<Socket = dyn Source> + Any {}
+
+// 可以在同一个 reactor 中混合 TCP 连接、UDP、监听 socket 和自定义 socket。
+// 例如 Reactor<AnySocket> 可以在一个线程中同时运行服务端和到上游的连接
+pub enum AnySocket {
+    Tcp(TcpConnection),
+    Udp(UdpSocket),
+    Acceptor(Acceptor<AnySocket>),
+    Custom(Box<dyn CustomSocket>),
+}
+
+macro_rules! dispatch {
+    ($self:expr, $socket:ident => $body:expr) => {
+        match $self {
+            AnySocket::Tcp($socket) => $body,
+            AnySocket::Udp($socket) => $body,
+            AnySocket::Acceptor($socket) => $body,
+            AnySocket::Custom($socket) => $body,
+        }
+    };
+}
+
+impl AnySocket {
+    pub fn custom<T>(socket: T) -> Self
+    where
+        T: CustomSocket,
+    {
+        AnySocket::Custom(Box::new(socket))
+    }
+
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: CustomSocket,
+    {
+        match self {
+            AnySocket::Custom(socket) => (&**socket as &dyn Any).downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn downcast_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: CustomSocket,
+    {
+        match self {
+            AnySocket::Custom(socket) => (&mut **socket as &mut dyn Any).downcast_mut(),
+            _ => None,
+        }
+    }
+}
+
+impl ReactorSocket for AnySocket {
+    type Socket = dyn Source;
+
+    fn socket(&mut self) -> &mut Self::Socket {
+        dispatch!(self, socket => socket.socket() as &mut dyn Source)
+    }
+
+    fn set_interest(&mut self, interest: mio::Interest) {
+        dispatch!(self, socket => socket.set_interest(interest))
+    }
+
+    fn interest(&self) -> mio::Interest {
+        dispatch!(self, socket => socket.interest())
+    }
+
+    fn handle_event(&mut self, event: &mio::event::Event, receive_time: Instant) {
+        dispatch!(self, socket => socket.handle_event(event, receive_time))
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        dispatch!(self, socket => socket.write(data))
+    }
+
+    fn stash_output(&mut self, data: &[u8]) {
+        dispatch!(self, socket => socket.stash_output(data))
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        dispatch!(self, socket => socket.write_vectored(bufs))
+    }
+
+    fn stash_chunk(&mut self, chunk: bytes::Bytes) {
+        dispatch!(self, socket => socket.stash_chunk(chunk))
+    }
+
+    fn handle_establish(&self, is_established: bool) {
+        dispatch!(self, socket => socket.handle_establish(is_established))
+    }
+
+    fn is_established(&self) -> bool {
+        dispatch!(self, socket => socket.is_established())
+    }
+
+    fn poll_token(&self) -> Option<mio::Token> {
+        dispatch!(self, socket => socket.poll_token())
+    }
+
+    fn set_poll_token(&mut self, token: mio::Token) {
+        dispatch!(self, socket => socket.set_poll_token(token))
+    }
+
+    fn send(&mut self, addr: SocketAddr, data: &[u8]) -> std::io::Result<usize> {
+        dispatch!(self, socket => socket.send(addr, data))
+    }
+
+    fn handle_write_complete(&mut self) {
+        dispatch!(self, socket => socket.handle_write_complete())
+    }
+
+    fn handle_shutdown(&mut self) -> bool {
+        dispatch!(self, socket => socket.handle_shutdown())
+    }
+
+    fn is_disconnecting(&self) -> bool {
+        dispatch!(self, socket => socket.is_disconnecting())
+    }
+}
+
+macro_rules! impl_as_socket {
+    ($variant:ident, $socket:ty) => {
+        impl From<$socket> for AnySocket {
+            fn from(socket: $socket) -> Self {
+                AnySocket::$variant(socket)
+            }
+        }
+
+        impl AsSocket<$socket> for AnySocket {
+            fn as_socket(&self) -> Option<&$socket> {
+                match self {
+                    AnySocket::$variant(socket) => Some(socket),
+                    _ => None,
+                }
+            }
+
+            fn as_socket_mut(&mut self) -> Option<&mut $socket> {
+                match self {
+                    AnySocket::$variant(socket) => Some(socket),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_as_socket!(Tcp, TcpConnection);
+impl_as_socket!(Udp, UdpSocket);
+impl_as_socket!(Acceptor, Acceptor<AnySocket>);
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex, mpsc},
+        time::{Duration, Instant},
+    };
+
+    use mio::{Interest, Token, event::Source, net::UnixDatagram};
+
+    use crate::{Acceptor, AnySocket, EventLoopThread, ReactorSocket, TcpConnection, UdpSocket};
+
+    struct Notifier {
+        socket: UnixDatagram,
+        tx: mpsc::Sender<Vec<u8>>,
+        poll_token: Option<Token>,
+    }
+
+    impl ReactorSocket for Notifier {
+        type Socket = dyn Source;
+
+        fn socket(&mut self) -> &mut Self::Socket {
+            &mut self.socket
+        }
+
+        fn set_interest(&mut self, _interest: Interest) {}
+
+        fn interest(&self) -> Interest {
+            Interest::READABLE
+        }
+
+        fn handle_event(&mut self, _event: &mio::event::Event, _receive_time: Instant) {
+            let mut buf = [0; 64];
+            while let Ok(n) = self.socket.recv(&mut buf) {
+                self.tx.send(buf[..n].to_vec()).unwrap();
+            }
+        }
+
+        fn write(&mut self, _data: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+
+        fn stash_output(&mut self, _data: &[u8]) {}
+
+        fn handle_establish(&self, _is_established: bool) {}
+
+        fn is_established(&self) -> bool {
+            self.poll_token.is_some()
+        }
+
+        fn poll_token(&self) -> Option<Token> {
+            self.poll_token
+        }
+
+        fn set_poll_token(&mut self, token: Token) {
+            self.poll_token = Some(token);
+        }
+
+        fn send(&mut self, _addr: std::net::SocketAddr, _data: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn test_single_loop() {
+        let mut event_loop_thread = EventLoopThread::<AnySocket>::new(8).unwrap();
+        let remote = event_loop_thread.get_remote();
+        event_loop_thread.run();
+        let (tx, rx) = mpsc::channel();
+
+        // TCP 监听和接受的连接在同一个 reactor 中
+        let listener = Acceptor::bind("127.0.0.1:0".parse().unwrap(), 16).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = Acceptor::with_listener(
+            listener,
+            vec![remote.clone()],
+            Arc::new(|_, _| {}),
+            Arc::new(|conn, buffer, _| {
+                conn.write(buffer.as_slice());
+                buffer.retrieve_all();
+            }),
+        );
+        remote.register(acceptor.into());
+
+        // 在 loop 线程中打开到上游的连接，注册到同一个 reactor
+        let upstream_tx = Mutex::new(tx.clone());
+        remote.run_in_loop(move |reactor| {
+            let stream = std::net::TcpStream::connect(addr).unwrap();
+            stream.set_nonblocking(true).unwrap();
+            let upstream = TcpConnection::new(
+                mio::net::TcpStream::from_std(stream),
+                Arc::new(|conn, is_connected| {
+                    if is_connected {
+                        conn.write(b"hello");
+                    }
+                }),
+                Arc::new(move |_, buffer, _| {
+                    let reply = buffer.retrieve_all_as_string();
+                    upstream_tx.lock().unwrap().send(reply).unwrap();
+                }),
+                Interest::READABLE,
+                reactor.get_sender(),
+            );
+            reactor.register(upstream.into()).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "hello");
+
+        let udp = mio::net::UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let udp_addr = udp.local_addr().unwrap();
+        let udp = UdpSocket::new(
+            udp,
+            Arc::new(|conn, data, peer, _| {
+                conn.send(peer, data);
+            }),
+            remote.get_sender(),
+        );
+        remote.register(udp.into());
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let mut buf = [0; 16];
+        let n = loop {
+            peer.send_to(b"ping", udp_addr).unwrap();
+            if let Ok(n) = peer.recv(&mut buf) {
+                break n;
+            }
+        };
+        assert_eq!(&buf[..n], b"ping");
+
+        // 自定义 fd 和定时器
+        let (notifier_socket, writer) = UnixDatagram::pair().unwrap();
+        let (notify_tx, notify_rx) = mpsc::channel();
+        let (token_tx, token_rx) = mpsc::channel();
+        remote.run_in_loop(move |reactor| {
+            let notifier = Notifier {
+                socket: notifier_socket,
+                tx: notify_tx,
+                poll_token: None,
+            };
+            let token = reactor.register(AnySocket::custom(notifier)).unwrap();
+            token_tx.send(token).unwrap();
+        });
+        let token = token_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        writer.send(b"event").unwrap();
+        assert_eq!(
+            notify_rx.recv_timeout(Duration::from_secs(1)).unwrap(),
+            b"event"
+        );
+
+        let downcast_tx = tx.clone();
+        remote.run_in_loop(move |reactor| {
+            let socket = reactor.socket_mut(token).unwrap();
+            let found = socket.downcast_mut::<Notifier>().is_some();
+            downcast_tx.send(found.to_string()).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "true");
+
+        remote.run_after(Duration::from_millis(10), move || {
+            tx.send("timer".to_string()).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "timer");
+
+        event_loop_thread.quit();
+        event_loop_thread.wait();
+    }
+}
@@ -0,0 +1,113 @@
+// 对比 Buffer 的查找和整理与旧的逐字节实现
+// 用法: buffer_bench [缓冲区大小] [重复次数]
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use simple_reactor::Buffer;
+
+// 旧实现：逐个位置比较，逐字节移动
+mod legacy {
+    pub fn find_bytes(data: &[u8], pattern: &[u8]) -> Option<usize> {
+        if pattern.is_empty() || data.len() < pattern.len() {
+            return None;
+        }
+        (0..=data.len() - pattern.len()).find(|&i| &data[i..i + pattern.len()] == pattern)
+    }
+
+    pub fn move_to_front(data: &mut [u8], start: usize) {
+        for i in 0..data.len() - start {
+            data[i] = data[start + i];
+        }
+    }
+}
+
+fn measure<F: FnMut()>(rounds: usize, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f();
+    }
+    start.elapsed()
+}
+
+fn print(name: &str, bytes: usize, elapsed: Duration) {
+    println!(
+        "{:<22} {:>8.1} ms {:>10.1} MB/s",
+        name,
+        elapsed.as_secs_f64() * 1000.0,
+        bytes as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+// 模拟 HTTP 头部：只有最后才出现分隔符，查找需要扫描全部数据
+fn request_like(size: usize) -> Vec<u8> {
+    let mut data: Vec<u8> = b"Header: value\r"
+        .iter()
+        .copied()
+        .cycle()
+        .take(size)
+        .collect();
+    data.extend_from_slice(b"\r\n\r\n");
+    data
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let size = args
+        .next()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(64 * 1024);
+    let rounds = args.next().and_then(|s| s.parse().ok()).unwrap_or(2000);
+    let total = size * rounds;
+    println!("{} bytes x {} rounds", size, rounds);
+
+    let data = request_like(size);
+    let mut buffer = Buffer::with_initial_size(data.len());
+    buffer.append(&data);
+
+    print(
+        "find_bytes (naive)",
+        total,
+        measure(rounds, || {
+            black_box(legacy::find_bytes(black_box(&data), b"\r\n\r\n"));
+        }),
+    );
+    print(
+        "find_bytes (memmem)",
+        total,
+        measure(rounds, || {
+            black_box(black_box(&buffer).find_bytes(b"\r\n\r\n"));
+        }),
+    );
+    print(
+        "find_crlf (naive)",
+        total,
+        measure(rounds, || {
+            black_box(legacy::find_bytes(black_box(&data), b"\r\n"));
+        }),
+    );
+    print(
+        "find_crlf (memmem)",
+        total,
+        measure(rounds, || {
+            black_box(black_box(&buffer).find_crlf());
+        }),
+    );
+
+    // 取走一个字节后追加，迫使 make_space 把剩余数据移到前面
+    let mut bytes = data.clone();
+    print(
+        "compact (byte loop)",
+        total,
+        measure(rounds, || legacy::move_to_front(black_box(&mut bytes), 1)),
+    );
+    print(
+        "compact (copy_within)",
+        total,
+        measure(rounds, || {
+            buffer.retrieve(1);
+            buffer.append(b"x");
+        }),
+    );
+}
@@ -0,0 +1,134 @@
+// 对比 reactor_channel 与旧的 Mutex<Vec> 实现的吞吐量
+// 用法: channel_bench [生产者线程数] [每个线程发送的消息数]
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use mio::{Events, Poll, Token, Waker};
+use simple_reactor::{reactor::LoopId, reactor_channel::Receiver};
+
+// 旧实现：每次发送都加锁并唤醒
+mod legacy {
+    use std::sync::{Arc, Mutex};
+
+    use mio::Waker;
+
+    pub struct Sender<T> {
+        pub queue: Arc<Mutex<Vec<T>>>,
+        pub waker: Arc<Waker>,
+    }
+
+    impl<T> Sender<T> {
+        pub fn send(&self, item: T) {
+            self.queue.lock().unwrap().push(item);
+            self.waker.wake().unwrap();
+        }
+    }
+
+    pub fn take_all<T>(queue: &Mutex<Vec<T>>) -> Vec<T> {
+        std::mem::take(&mut *queue.lock().unwrap())
+    }
+}
+
+struct Report {
+    elapsed: Duration,
+    wakeups: usize,
+}
+
+// 消费者模拟 reactor 的循环：poll 等待唤醒，然后取走全部信号
+fn consume<F>(mut poll: Poll, total: usize, mut take_all: F) -> Report
+where
+    F: FnMut() -> usize,
+{
+    let mut events = Events::with_capacity(16);
+    let mut received = 0;
+    let mut wakeups = 0;
+    let start = Instant::now();
+    while received < total {
+        poll.poll(&mut events, Some(Duration::from_millis(100)))
+            .unwrap();
+        wakeups += events.iter().count();
+        received += take_all();
+    }
+    Report {
+        elapsed: start.elapsed(),
+        wakeups,
+    }
+}
+
+fn produce<F>(producers: usize, per_producer: usize, send: F) -> Vec<thread::JoinHandle<()>>
+where
+    F: Fn(u64) + Send + Sync + 'static,
+{
+    let send = Arc::new(send);
+    (0..producers)
+        .map(|_| {
+            let send = send.clone();
+            thread::spawn(move || (0..per_producer as u64).for_each(|i| send(i)))
+        })
+        .collect()
+}
+
+fn bench_legacy(producers: usize, per_producer: usize) -> Report {
+    let poll = Poll::new().unwrap();
+    let waker = Arc::new(Waker::new(poll.registry(), Token(0)).unwrap());
+    let queue = Arc::new(Mutex::new(Vec::new()));
+    let sender = legacy::Sender {
+        queue: queue.clone(),
+        waker,
+    };
+    let handles = produce(producers, per_producer, move |i| sender.send(i));
+    let report = consume(poll, producers * per_producer, || {
+        legacy::take_all(&queue).len()
+    });
+    handles.into_iter().for_each(|h| h.join().unwrap());
+    report
+}
+
+fn bench_lock_free(producers: usize, per_producer: usize) -> Report {
+    let poll = Poll::new().unwrap();
+    let waker = Arc::new(Waker::new(poll.registry(), Token(0)).unwrap());
+    let mut receiver = Receiver::new();
+    // 生产者都不是 loop 线程
+    let sender = receiver.sender(waker, LoopId::next());
+    let handles = produce(producers, per_producer, move |i| {
+        // 与 SocketRemote::write 相同，走数据信号的路径
+        sender.try_send(i).unwrap()
+    });
+    let report = consume(poll, producers * per_producer, || receiver.take_all().len());
+    handles.into_iter().for_each(|h| h.join().unwrap());
+    report
+}
+
+fn print(name: &str, total: usize, report: &Report) {
+    println!(
+        "{:<10} {:>8.1} ms {:>12.0} msg/s {:>8} wakeups",
+        name,
+        report.elapsed.as_secs_f64() * 1000.0,
+        total as f64 / report.elapsed.as_secs_f64(),
+        report.wakeups
+    );
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let producers = args.next().and_then(|s| s.parse().ok()).unwrap_or(4);
+    let per_producer = args
+        .next()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1_000_000);
+    let total = producers * per_producer;
+    println!("{} producers x {} messages", producers, per_producer);
+
+    for round in 0..3 {
+        println!("round {}", round);
+        print("mutex", total, &bench_legacy(producers, per_producer));
+        print(
+            "lock-free",
+            total,
+            &bench_lock_free(producers, per_producer),
+        );
+    }
+}
@@ -1,10 +1,10 @@
-use log::info;
+use log::{error, info};
 use simple_reactor::{
     Buffer, Server, SocketRemote, TcpConnection, UdpSocket, server::ServerQuiter,
 };
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
-static mut SERVER_QUITER: Option<ServerQuiter> = None;
+static SERVER_QUITER: OnceLock<ServerQuiter> = OnceLock::new();
 
 fn message_callback(
     remote: Arc<SocketRemote<TcpConnection>>,
@@ -22,12 +22,10 @@ fn message_callback(
     if String::from_utf8_lossy(&content).contains("shutdown") {
         remote.shutdown();
     }
-    if String::from_utf8_lossy(&content).contains("KILL") {
-        unsafe {
-            if let Some(quiter) = &(*&raw const SERVER_QUITER) {
-                quiter.quit();
-            }
-        }
+    if String::from_utf8_lossy(&content).contains("KILL")
+        && let Some(quiter) = SERVER_QUITER.get()
+    {
+        quiter.quit();
     }
 }
 
@@ -48,12 +46,10 @@ fn datagram_callback(
     let content = String::from_utf8_lossy(data);
     info!("Received datagram from {}: {}", addr, content);
     remote.send(addr, data);
-    if content.contains("KILL") {
-        unsafe {
-            if let Some(quiter) = &(*&raw const SERVER_QUITER) {
-                quiter.quit();
-            }
-        }
+    if content.contains("KILL")
+        && let Some(quiter) = SERVER_QUITER.get()
+    {
+        quiter.quit();
     }
 }
 
@@ -62,17 +58,19 @@ fn main() {
     info!("env_logger inited");
 
     let addr = "127.0.0.1:8888".to_string();
-    let server = Server::tcp_udp_server(
-        addr.clone(),
-        4,
-        Arc::new(message_callback),
-        Arc::new(connection_callback),
-        Arc::new(datagram_callback),
-    );
+    let server = Server::builder()
+        .tcp(addr.clone())
+        .udp(addr)
+        .io_threads(4)
+        .message_callback(Arc::new(message_callback))
+        .connection_callback(Arc::new(connection_callback))
+        .datagram_callback(Arc::new(datagram_callback))
+        .build()
+        .expect("Failed to build server");
 
-    unsafe {
-        SERVER_QUITER = Some(server.get_quiter());
-    }
+    let _ = SERVER_QUITER.set(server.get_quiter());
 
-    server.run();
+    if let Err(e) = server.run() {
+        error!("Server stopped: {}", e);
+    }
 }
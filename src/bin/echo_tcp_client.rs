@@ -24,7 +24,8 @@ fn main() {
         addr,
         Arc::new(message_callback),
         Arc::new(default_connection_callback),
-    );
+    )
+    .expect("Failed to connect server");
     client.listen();
 
     loop {
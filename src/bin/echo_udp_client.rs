@@ -24,7 +24,8 @@ fn main() {
         mio::net::UdpSocket::bind(addr.clone().parse().unwrap())
             .expect("Failed to bind UDP socket"),
         Arc::new(datagram_callback),
-    );
+    )
+    .expect("Failed to create client");
     client.listen();
 
     loop {
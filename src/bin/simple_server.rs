@@ -1,4 +1,4 @@
-use log::info;
+use log::{error, info};
 use simple_reactor::{Buffer, Server, SocketRemote, TcpConnection};
 use std::sync::Arc;
 
@@ -29,11 +29,14 @@ fn main() {
     info!("env_logger inited");
 
     let addr = "127.0.0.1:8888".to_string();
-    let server = Server::tcp_server(
-        addr.clone(),
-        4,
-        Arc::new(message_callback),
-        Arc::new(connection_callback),
-    );
-    server.run();
+    let server = Server::builder()
+        .tcp(addr)
+        .io_threads(4)
+        .message_callback(Arc::new(message_callback))
+        .connection_callback(Arc::new(connection_callback))
+        .build()
+        .expect("Failed to build server");
+    if let Err(e) = server.run() {
+        error!("Server stopped: {}", e);
+    }
 }
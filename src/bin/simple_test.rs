@@ -13,9 +13,9 @@ fn main() {
     let num_per_sec = 10;
     let mut index = 0;
     let mut streams = Vec::new();
-    streams.resize_with(keep_secs, || Vec::new());
+    streams.resize_with(keep_secs, Vec::new);
     let mut udps = Vec::new();
-    udps.resize_with(keep_secs, || Vec::new());
+    udps.resize_with(keep_secs, Vec::new);
 
     loop {
         streams[index] = Vec::new();
@@ -30,8 +30,8 @@ fn main() {
         for (i, v) in streams.iter_mut().enumerate() {
             for (j, stream) in v.iter_mut().enumerate() {
                 let msg = format!("Hi, I am stream[{}][{}]", i, j);
-                let mut buf = msg.as_bytes().to_owned();
-                if stream.write(&mut buf).is_ok() {
+                let buf = msg.as_bytes().to_owned();
+                if stream.write(&buf).is_ok() {
                     total_send += 1;
                 }
             }
@@ -1,13 +1,30 @@
 use bytes::BytesMut;
-use std::io::{self, Read, Write};
-
-const INITIAL_SIZE: usize = 1024;
+use memchr::memmem::Finder;
+use std::{
+    cell::RefCell,
+    io::{self, IoSliceMut, Read, Write},
+    sync::LazyLock,
+};
+
+pub const INITIAL_SIZE: usize = 1024;
 const PREPEND_SIZE: usize = 8;
+// 读取时额外使用的空间，可写空间不够时多出的数据先读到这里
+const EXTRA_BUFFER_SIZE: usize = 65536;
+// u64 的 varint 最多 10 个字节
+const MAX_VARINT_LEN: usize = 10;
+
+thread_local! {
+    // 每个线程只分配一次，避免每次读取都在栈上清零 64KB
+    static EXTRA_BUFFER: RefCell<Box<[u8]>> =
+        RefCell::new(vec![0; EXTRA_BUFFER_SIZE].into_boxed_slice());
+}
 
 pub struct Buffer {
     buffer: BytesMut,
     reader_index: usize,
     writer_index: usize,
+    // 可读数据的上限，超过时拒绝写入
+    max_capacity: Option<usize>,
 }
 
 impl Buffer {
@@ -24,27 +41,81 @@ impl Buffer {
             buffer,
             reader_index: PREPEND_SIZE,
             writer_index: PREPEND_SIZE,
+            max_capacity: None,
         }
     }
 
+    // 设置后最多保存 max_capacity 字节的可读数据：try_append 和 read_from 返回 OutOfMemory，
+    // append 和其它追加方法返回 false，都不写入任何数据
+    pub fn set_max_capacity(&mut self, max_capacity: Option<usize>) {
+        self.max_capacity = max_capacity;
+    }
+
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.max_capacity
+    }
+
+    // 已分配的空间，不包括前置空间
+    pub fn capacity(&self) -> usize {
+        self.buffer.len() - PREPEND_SIZE
+    }
+
+    // 释放多余的空间，保留不少于 min_capacity 且能容纳可读数据的空间
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let readable = self.readable_bytes();
+        let capacity = readable.max(min_capacity);
+        if capacity >= self.capacity() {
+            return;
+        }
+        let mut buffer = BytesMut::with_capacity(PREPEND_SIZE + capacity);
+        buffer.resize(PREPEND_SIZE + capacity, 0);
+        buffer[PREPEND_SIZE..PREPEND_SIZE + readable].copy_from_slice(self.as_slice());
+        self.buffer = buffer;
+        self.reader_index = PREPEND_SIZE;
+        self.writer_index = PREPEND_SIZE + readable;
+    }
+
+    // 还能追加的字节数
+    pub(crate) fn remaining_capacity(&self) -> usize {
+        self.max_capacity
+            .map_or(usize::MAX, |max| max.saturating_sub(self.readable_bytes()))
+    }
+
     pub fn read_tcp_stream(&mut self, stream: &mut mio::net::TcpStream) -> std::io::Result<usize> {
-        let writable_start = self.writer_index;
+        self.read_from(stream)
+    }
 
-        let mut bytes_read = stream.read(&mut self.buffer[writable_start..])?;
-        if bytes_read > 0 {
+    // 一次 read_vectored 同时读入可写区域和线程内复用的额外空间，
+    // 缓冲区不必预先分配很大，每次读取也只有一次系统调用 (readv)
+    // 设置了容量上限时最多读到上限，已满时返回 OutOfMemory
+    pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let remaining = self.remaining_capacity();
+        if remaining == 0 {
+            return Err(capacity_exceeded());
+        }
+        let writable = self.writable_bytes().min(remaining);
+        let extra = EXTRA_BUFFER_SIZE.min(remaining - writable);
+        let start = self.writer_index;
+        // 可写空间已经足够大时不使用额外空间
+        if writable >= EXTRA_BUFFER_SIZE || extra == 0 {
+            let bytes_read = reader.read(&mut self.buffer[start..start + writable])?;
             self.writer_index += bytes_read;
+            return Ok(bytes_read);
         }
-
-        if self.writable_bytes() == 0 {
-            let mut extra_buffer = [0; 65536]; // 64KB
-            let extra_bytes_read = stream.read(&mut extra_buffer)?;
-            if extra_bytes_read > 0 {
-                self.append(&extra_buffer[..extra_bytes_read]);
-                bytes_read += extra_bytes_read;
+        EXTRA_BUFFER.with_borrow_mut(|extra_buffer| {
+            let mut bufs = [
+                IoSliceMut::new(&mut self.buffer[start..start + writable]),
+                IoSliceMut::new(&mut extra_buffer[..extra]),
+            ];
+            let bytes_read = reader.read_vectored(&mut bufs)?;
+            if bytes_read <= writable {
+                self.writer_index += bytes_read;
+            } else {
+                self.writer_index += writable;
+                self.append(&extra_buffer[..bytes_read - writable]);
             }
-        }
-
-        Ok(bytes_read)
+            Ok(bytes_read)
+        })
     }
 
     pub fn peek(&self, len: usize) -> Option<&[u8]> {
@@ -87,17 +158,42 @@ impl Buffer {
         self.retrieve_as_string(len).unwrap_or_default()
     }
 
-    pub fn append(&mut self, data: &[u8]) {
+    // 超过 set_max_capacity 设置的上限时不写入并返回 false
+    pub fn append(&mut self, data: &[u8]) -> bool {
+        self.try_append(data).is_ok()
+    }
+
+    // 超过容量上限时不写入任何数据并返回 OutOfMemory
+    pub fn try_append(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() > self.remaining_capacity() {
+            return Err(capacity_exceeded());
+        }
         self.ensure_writable_bytes(data.len());
 
         let start = self.writer_index;
         let end = start + data.len();
         self.buffer[start..end].copy_from_slice(data);
         self.writer_index += data.len();
+        Ok(())
+    }
+
+    pub fn append_string(&mut self, s: &str) -> bool {
+        self.append(s.as_bytes())
+    }
+
+    pub fn try_append_string(&mut self, s: &str) -> io::Result<()> {
+        self.try_append(s.as_bytes())
     }
 
-    pub fn append_string(&mut self, s: &str) {
-        self.append(s.as_bytes());
+    // 写入可读数据前面的预留空间，例如在消息体之前补上长度头。空间不足时返回 false
+    pub fn prepend(&mut self, data: &[u8]) -> bool {
+        if data.len() > self.prepend_bytes() || data.len() > self.remaining_capacity() {
+            return false;
+        }
+        self.reader_index -= data.len();
+        let start = self.reader_index;
+        self.buffer[start..start + data.len()].copy_from_slice(data);
+        true
     }
 
     pub fn readable_bytes(&self) -> usize {
@@ -127,10 +223,8 @@ impl Buffer {
         if self.writable_bytes() + self.prepend_bytes() - PREPEND_SIZE >= len {
             // 移动数据到前面
             let reader_start = self.reader_index;
-
-            for i in 0..readable {
-                self.buffer[PREPEND_SIZE + i] = self.buffer[reader_start + i];
-            }
+            self.buffer
+                .copy_within(reader_start..reader_start + readable, PREPEND_SIZE);
 
             self.reader_index = PREPEND_SIZE;
             self.writer_index = PREPEND_SIZE + readable;
@@ -141,30 +235,23 @@ impl Buffer {
         }
     }
 
-    // 查找特定字节序列
+    // 查找特定字节序列，单字节用 memchr，多字节用 two-way 算法，都是线性时间
     pub fn find_bytes(&self, pattern: &[u8]) -> Option<usize> {
-        if pattern.is_empty() || self.readable_bytes() < pattern.len() {
-            return None;
-        }
-
-        let data = &self.buffer[self.reader_index..self.writer_index];
-
-        for i in 0..=data.len() - pattern.len() {
-            if &data[i..i + pattern.len()] == pattern {
-                return Some(i);
-            }
+        match pattern {
+            [] => None,
+            [byte] => memchr::memchr(*byte, self.as_slice()),
+            _ => memchr::memmem::find(self.as_slice(), pattern),
         }
-
-        None
     }
 
     // 查找换行符
     pub fn find_crlf(&self) -> Option<usize> {
-        self.find_bytes(b"\r\n")
+        static CRLF: LazyLock<Finder<'static>> = LazyLock::new(|| Finder::new(b"\r\n"));
+        CRLF.find(self.as_slice())
     }
 
     pub fn find_lf(&self) -> Option<usize> {
-        self.find_bytes(b"\n")
+        memchr::memchr(b'\n', self.as_slice())
     }
 
     // 读取到指定分隔符为止
@@ -214,15 +301,135 @@ impl Buffer {
     }
 }
 
+// 整数按网络字节序（大端）读写。peek 和 read 在数据不足时返回 None，read 成功后取走数据
+macro_rules! int_methods {
+    ($($ty:ty => $append:ident, $try_append:ident, $peek:ident, $read:ident, $prepend:ident;)*) => {
+        $(
+            pub fn $append(&mut self, value: $ty) -> bool {
+                self.append(&value.to_be_bytes())
+            }
+
+            pub fn $try_append(&mut self, value: $ty) -> io::Result<()> {
+                self.try_append(&value.to_be_bytes())
+            }
+
+            pub fn $peek(&self) -> Option<$ty> {
+                let bytes = self.peek(size_of::<$ty>())?;
+                Some(<$ty>::from_be_bytes(bytes.try_into().unwrap()))
+            }
+
+            pub fn $read(&mut self) -> Option<$ty> {
+                let value = self.$peek()?;
+                self.retrieve(size_of::<$ty>());
+                Some(value)
+            }
+
+            pub fn $prepend(&mut self, value: $ty) -> bool {
+                self.prepend(&value.to_be_bytes())
+            }
+        )*
+    };
+}
+
+impl Buffer {
+    int_methods! {
+        u8 => append_u8, try_append_u8, peek_u8, read_u8, prepend_u8;
+        u16 => append_u16, try_append_u16, peek_u16, read_u16, prepend_u16;
+        u32 => append_u32, try_append_u32, peek_u32, read_u32, prepend_u32;
+        u64 => append_u64, try_append_u64, peek_u64, read_u64, prepend_u64;
+        i8 => append_i8, try_append_i8, peek_i8, read_i8, prepend_i8;
+        i16 => append_i16, try_append_i16, peek_i16, read_i16, prepend_i16;
+        i32 => append_i32, try_append_i32, peek_i32, read_i32, prepend_i32;
+        i64 => append_i64, try_append_i64, peek_i64, read_i64, prepend_i64;
+    }
+
+    // LEB128 编码，每个字节低 7 位为数据，最高位表示后面还有字节
+    pub fn append_varint(&mut self, value: u64) -> bool {
+        self.try_append_varint(value).is_ok()
+    }
+
+    pub fn try_append_varint(&mut self, value: u64) -> io::Result<()> {
+        let mut bytes = [0; MAX_VARINT_LEN];
+        let len = encode_varint(value, &mut bytes);
+        self.try_append(&bytes[..len])
+    }
+
+    // 返回值和占用的字节数。数据不完整时返回 Ok(None)，超过 10 个字节或溢出 u64 时返回 InvalidData
+    pub fn peek_varint(&self) -> io::Result<Option<(u64, usize)>> {
+        let mut value = 0;
+        for (i, &byte) in self.as_slice().iter().take(MAX_VARINT_LEN).enumerate() {
+            if i == MAX_VARINT_LEN - 1 && byte > 1 {
+                break;
+            }
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte < 0x80 {
+                return Ok(Some((value, i + 1)));
+            }
+        }
+        if self.readable_bytes() < MAX_VARINT_LEN {
+            return Ok(None);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "varint overflows u64",
+        ))
+    }
+
+    pub fn read_varint(&mut self) -> io::Result<Option<u64>> {
+        let Some((value, len)) = self.peek_varint()? else {
+            return Ok(None);
+        };
+        self.retrieve(len);
+        Ok(Some(value))
+    }
+
+    // 有符号数先做 zigzag 编码，绝对值小的负数也只占很少的字节
+    pub fn append_varint_i64(&mut self, value: i64) -> bool {
+        self.append_varint(zigzag_encode(value))
+    }
+
+    pub fn try_append_varint_i64(&mut self, value: i64) -> io::Result<()> {
+        self.try_append_varint(zigzag_encode(value))
+    }
+
+    pub fn read_varint_i64(&mut self) -> io::Result<Option<i64>> {
+        Ok(self.read_varint()?.map(zigzag_decode))
+    }
+}
+
+// 返回编码后的字节数
+fn encode_varint(mut value: u64, bytes: &mut [u8; MAX_VARINT_LEN]) -> usize {
+    let mut len = 0;
+    while value >= 0x80 {
+        bytes[len] = value as u8 | 0x80;
+        value >>= 7;
+        len += 1;
+    }
+    bytes[len] = value as u8;
+    len + 1
+}
+
+pub fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub fn zigzag_decode(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
 impl Default for Buffer {
     fn default() -> Self {
         Self::new()
     }
 }
 
+pub(crate) fn capacity_exceeded() -> io::Error {
+    io::Error::new(io::ErrorKind::OutOfMemory, "buffer capacity exceeded")
+}
+
 impl Write for Buffer {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.append(buf);
+        self.try_append(buf)?;
         Ok(buf.len())
     }
 
@@ -325,6 +532,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::write_literal)]
     fn test_write_trait() {
         let mut buffer = Buffer::new();
         write!(buffer, "Hello, {}!", "World").unwrap();
@@ -368,4 +576,199 @@ mod tests {
         assert_eq!(world, ", World!");
         assert_eq!(buffer.readable_bytes(), 0);
     }
+
+    #[test]
+    fn test_integers() {
+        let mut buffer = Buffer::new();
+        buffer.append_u8(0xab);
+        buffer.append_u16(0x1234);
+        buffer.append_u32(0xdeadbeef);
+        buffer.append_i64(-2);
+        assert_eq!(
+            &buffer.as_slice()[..7],
+            &[0xab, 0x12, 0x34, 0xde, 0xad, 0xbe, 0xef]
+        );
+
+        assert_eq!(buffer.peek_u8(), Some(0xab));
+        assert_eq!(buffer.read_u8(), Some(0xab));
+        assert_eq!(buffer.read_u16(), Some(0x1234));
+        assert_eq!(buffer.peek_i32(), Some(0xdeadbeef_u32 as i32));
+        assert_eq!(buffer.read_u32(), Some(0xdeadbeef));
+        assert_eq!(buffer.peek_u64(), Some(u64::MAX - 1));
+        assert_eq!(buffer.read_i64(), Some(-2));
+
+        // 数据不足时不取走任何数据
+        buffer.append(&[1, 2, 3]);
+        assert_eq!(buffer.read_u32(), None);
+        assert_eq!(buffer.readable_bytes(), 3);
+    }
+
+    #[test]
+    fn test_prepend() {
+        let mut buffer = Buffer::new();
+        buffer.append(b"body");
+        assert!(buffer.prepend_u32(4));
+        assert_eq!(buffer.as_slice(), b"\0\0\0\x04body");
+        assert!(buffer.prepend_u16(1));
+        assert_eq!(buffer.prepend_bytes(), 2);
+        // 预留空间只剩 2 个字节
+        assert!(!buffer.prepend_u32(0));
+        assert_eq!(buffer.read_u16(), Some(1));
+        assert_eq!(buffer.read_u32(), Some(4));
+        assert_eq!(buffer.retrieve_all_as_string(), "body");
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut buffer = Buffer::new();
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            buffer.append_varint(value);
+        }
+        assert_eq!(&buffer.as_slice()[..5], &[0, 1, 0x7f, 0x80, 0x01]);
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            assert_eq!(buffer.read_varint().unwrap(), Some(value));
+        }
+
+        for value in [0, -1, 1, -64, 64, i64::MIN, i64::MAX] {
+            buffer.append_varint_i64(value);
+            assert_eq!(buffer.read_varint_i64().unwrap(), Some(value));
+        }
+        assert_eq!(zigzag_encode(-1), 1);
+        assert_eq!(zigzag_encode(1), 2);
+
+        // 不完整
+        buffer.append(&[0x80, 0x80]);
+        assert_eq!(buffer.read_varint().unwrap(), None);
+        assert_eq!(buffer.readable_bytes(), 2);
+
+        // 超过 10 个字节或溢出
+        buffer.retrieve_all();
+        buffer.append(&[0xff; 11]);
+        assert!(buffer.read_varint().is_err());
+        buffer.retrieve_all();
+        buffer.append(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]);
+        assert!(buffer.read_varint().is_err());
+    }
+
+    // 记录每次调用，验证只有一次 read_vectored
+    struct CountingReader {
+        data: io::Cursor<Vec<u8>>,
+        calls: usize,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.calls += 1;
+            self.data.read(buf)
+        }
+
+        fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            self.data.read_vectored(bufs)
+        }
+    }
+
+    #[test]
+    fn test_read_from() {
+        let data: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+        let mut reader = CountingReader {
+            data: io::Cursor::new(data.clone()),
+            calls: 0,
+        };
+        let mut buffer = Buffer::with_initial_size(16);
+        let n = buffer.read_from(&mut reader).unwrap();
+        assert_eq!(n, 16 + EXTRA_BUFFER_SIZE);
+        assert_eq!(reader.calls, 1);
+        assert_eq!(buffer.as_slice(), &data[..n]);
+
+        // 可写空间足够时不使用额外空间
+        buffer.retrieve_all();
+        let n = buffer.read_from(&mut reader).unwrap();
+        assert_eq!(n, data.len() - 16 - EXTRA_BUFFER_SIZE);
+        assert_eq!(buffer.as_slice(), &data[16 + EXTRA_BUFFER_SIZE..]);
+        assert_eq!(buffer.read_from(&mut reader).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_read_from_unix_stream() {
+        let (mut writer, mut reader) = std::os::unix::net::UnixStream::pair().unwrap();
+        writer.write_all(b"over unix socket").unwrap();
+        let mut buffer = Buffer::new();
+        assert_eq!(buffer.read_from(&mut reader).unwrap(), 16);
+        assert_eq!(buffer.retrieve_all_as_string(), "over unix socket");
+    }
+
+    #[test]
+    fn test_compaction_and_shrink() {
+        let mut buffer = Buffer::with_initial_size(16);
+        buffer.append(b"0123456789abcdef");
+        buffer.retrieve(10);
+        // 前面空出的空间足够时移动数据而不是扩容
+        buffer.append(b"ghijklmn");
+        assert_eq!(buffer.capacity(), 16);
+        assert_eq!(buffer.as_slice(), b"abcdefghijklmn");
+
+        buffer.append(&[b'x'; 100_000]);
+        buffer.retrieve(100_000);
+        assert!(buffer.capacity() >= 100_014);
+        buffer.shrink_to(INITIAL_SIZE);
+        assert_eq!(buffer.capacity(), INITIAL_SIZE);
+        assert_eq!(buffer.as_slice(), &[b'x'; 14]);
+
+        // 不会丢弃可读数据
+        buffer.shrink_to(0);
+        assert_eq!(buffer.capacity(), 14);
+        assert_eq!(buffer.as_slice(), &[b'x'; 14]);
+        assert!(buffer.prepend_u8(b'>'));
+        assert_eq!(buffer.read_u8(), Some(b'>'));
+    }
+
+    #[test]
+    fn test_max_capacity() {
+        let mut buffer = Buffer::new();
+        buffer.set_max_capacity(Some(8));
+        buffer.append(b"12345");
+        let err = buffer.try_append(b"6789").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+        assert!(write!(buffer, "6789").is_err());
+        assert_eq!(buffer.as_slice(), b"12345");
+        assert!(!buffer.prepend(b"abcd"));
+
+        // 读取时最多读到上限
+        let mut reader = io::Cursor::new(b"abcdef".to_vec());
+        assert_eq!(buffer.read_from(&mut reader).unwrap(), 3);
+        assert_eq!(buffer.as_slice(), b"12345abc");
+        let err = buffer.read_from(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+
+        buffer.retrieve(4);
+        assert_eq!(buffer.read_from(&mut reader).unwrap(), 3);
+        assert_eq!(buffer.as_slice(), b"5abcdef");
+
+        // 类型化的追加方法超过上限时同样不写入
+        assert!(buffer.try_append_u8(1).is_ok());
+        assert!(buffer.try_append_u16(1).is_err());
+        assert!(buffer.try_append_string("xy").is_err());
+        assert!(buffer.try_append_varint(300).is_err());
+        assert!(!buffer.append(b"x"));
+        assert!(!buffer.append_u32(1));
+        assert!(!buffer.append_varint_i64(-1));
+        assert_eq!(buffer.readable_bytes(), 8);
+    }
+
+    #[test]
+    fn test_find() {
+        let mut buffer = Buffer::new();
+        buffer.append(b"xx\rab\r\nline\n");
+        buffer.retrieve(1);
+        assert_eq!(buffer.find_crlf(), Some(4));
+        assert_eq!(buffer.find_lf(), Some(5));
+        assert_eq!(buffer.find_bytes(b"a"), Some(2));
+        assert_eq!(buffer.find_bytes(b"line\n"), Some(6));
+        assert_eq!(buffer.find_bytes(b""), None);
+        assert_eq!(buffer.find_bytes(b"line\n\n"), None);
+        assert_eq!(buffer.read_until(b"\r\n").unwrap(), b"x\rab");
+        assert_eq!(buffer.read_until(b"\n").unwrap(), b"line");
+        assert_eq!(buffer.read_until(b"\n"), None);
+    }
 }
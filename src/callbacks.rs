@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, sync::Arc, time::Instant};
+use std::{io, net::SocketAddr, sync::Arc, time::Instant};
 
 use log::info;
 
@@ -7,6 +7,13 @@ use crate::{Buffer, SocketRemote, TcpConnection, UdpSocket};
 pub type ConnectionCallback = Arc<dyn Fn(Arc<SocketRemote<TcpConnection>>, bool) + Sync + Send>;
 pub type MessageCallback =
     Arc<dyn Fn(Arc<SocketRemote<TcpConnection>>, &mut Buffer, Instant) + Sync + Send>;
+pub type WriteCompleteCallback = Arc<dyn Fn(Arc<SocketRemote<TcpConnection>>) + Sync + Send>;
+pub type HighWaterMarkCallback = Arc<dyn Fn(Arc<SocketRemote<TcpConnection>>, usize) + Sync + Send>;
+// 连接被拒绝或超时，参数为目标地址和错误
+pub type ConnectErrorCallback = Arc<dyn Fn(SocketAddr, io::Error) + Sync + Send>;
+pub type IdleCallback = Arc<dyn Fn(Arc<SocketRemote<TcpConnection>>) + Sync + Send>;
+// 分帧层解出的一条消息
+pub type FrameCallback<T> = Arc<dyn Fn(Arc<SocketRemote<TcpConnection>>, T, Instant) + Sync + Send>;
 pub type DatagramCallback =
     Arc<dyn Fn(Arc<SocketRemote<UdpSocket>>, &mut [u8], SocketAddr, Instant) + Sync + Send>;
 
@@ -1,14 +1,18 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 
 use crate::callbacks::{ConnectionCallback, DatagramCallback, MessageCallback};
-use crate::{EventLoopThread, Reactor, ReactorSocket, SocketRemote, TcpConnection, UdpSocket};
+use crate::connector::Connector;
+use crate::error::{Result, parse_addr};
+use crate::{
+    EventLoopThread, Reactor, ReactorSocket, SocketRemote, TcpConnection, TcpOptions, UdpSocket,
+};
 
 pub struct Client<S>
 where
     S: ReactorSocket + 'static,
 {
     event_loop_thread: EventLoopThread<S>,
-    remote: SocketRemote<S>,
+    remote: Arc<SocketRemote<S>>,
 }
 
 impl<S> Client<S>
@@ -19,30 +23,30 @@ where
         self.event_loop_thread.run();
     }
 
+    // 与回调中收到的是同一个 SocketRemote
+    pub fn remote(&self) -> &Arc<SocketRemote<S>> {
+        &self.remote
+    }
+
     pub fn shutdown(self) {
         self.event_loop_thread.quit();
     }
 }
 
 impl Client<UdpSocket> {
-    pub fn new(udp_socket: mio::net::UdpSocket, datagram_callback: DatagramCallback) -> Self {
-        let local_addr = udp_socket.local_addr().unwrap();
-        let mut reactor = Reactor::<UdpSocket>::new(2);
-        let sender = reactor.get_sender();
-        let socket = UdpSocket::new(udp_socket, datagram_callback, sender.clone());
-        let socket_status = socket.is_established.clone();
-        let token = reactor.register(socket).unwrap();
+    pub fn new(
+        udp_socket: mio::net::UdpSocket,
+        datagram_callback: DatagramCallback,
+    ) -> Result<Self> {
+        let mut reactor = Reactor::<UdpSocket>::new(2)?;
+        let socket = UdpSocket::new(udp_socket, datagram_callback, reactor.get_sender());
+        let token = reactor.register(socket)?;
+        let remote = reactor.socket(token).unwrap().remote().clone();
         let event_loop_thread = EventLoopThread::with_reactor(reactor);
-        Self {
+        Ok(Self {
             event_loop_thread,
-            remote: SocketRemote::new(
-                local_addr,
-                SocketAddr::from(([0, 0, 0, 0], 0)),
-                token,
-                sender,
-                socket_status,
-            ),
-        }
+            remote,
+        })
     }
 
     pub fn send(&self, addr: SocketAddr, data: &[u8]) -> bool {
@@ -55,26 +59,43 @@ impl Client<TcpConnection> {
         addr: String,
         message_callback: MessageCallback,
         connection_callback: ConnectionCallback,
-    ) -> Self {
-        let mut reactor = Reactor::<TcpConnection>::new(2);
-        let sender = reactor.get_sender();
-        let stream = mio::net::TcpStream::connect(addr.parse().unwrap()).unwrap();
-        let local_addr = stream.local_addr().unwrap();
-        let peer_addr = stream.peer_addr().unwrap();
-        let socket = TcpConnection::new(
-            stream,
-            connection_callback,
+    ) -> Result<Self> {
+        Self::with_options(
+            addr,
+            TcpOptions::default(),
             message_callback,
-            mio::Interest::READABLE,
-            sender.clone(),
-        );
-        let socket_status = socket.is_established.clone();
-        let token = reactor.register(socket).unwrap();
+            connection_callback,
+        )
+    }
+
+    // 发起连接后、注册到 reactor 之前设置 socket 选项
+    pub fn with_options(
+        addr: String,
+        options: TcpOptions,
+        message_callback: MessageCallback,
+        connection_callback: ConnectionCallback,
+    ) -> Result<Self> {
+        let connector = Connector::new(parse_addr(&addr)?).tcp_options(options);
+        Self::connect(connector, message_callback, connection_callback)
+    }
+
+    // 非阻塞连接，返回时连接可能还未完成，连接成功后才能写入。
+    // 连接被拒绝或超时时调用 connector 的 error_callback，不调用 connection_callback
+    pub fn connect(
+        connector: Connector,
+        message_callback: MessageCallback,
+        connection_callback: ConnectionCallback,
+    ) -> Result<Self> {
+        let mut reactor = Reactor::<TcpConnection>::new(2)?;
+        let socket =
+            connector.connect(connection_callback, message_callback, reactor.get_sender())?;
+        let token = reactor.register(socket)?;
+        let remote = reactor.socket(token).unwrap().remote().clone();
         let event_loop_thread = EventLoopThread::with_reactor(reactor);
-        Self {
+        Ok(Self {
             event_loop_thread,
-            remote: SocketRemote::new(local_addr, peer_addr, token, sender, socket_status),
-        }
+            remote,
+        })
     }
 
     pub fn write(&self, data: &[u8]) -> bool {
@@ -0,0 +1,363 @@
+use std::io;
+
+use bytes::Bytes;
+
+use crate::{Buffer, buffer::capacity_exceeded};
+
+// 从输入缓冲区中解出一条消息，数据不完整时返回 Ok(None) 并保留已收到的部分。
+// 返回 Err 表示对端违反协议，连接会被关闭
+pub trait Decoder {
+    type Item;
+
+    fn decode(&mut self, buffer: &mut Buffer) -> io::Result<Option<Self::Item>>;
+}
+
+// 把消息编码后追加到 buffer
+pub trait Encoder<Item> {
+    fn encode(&mut self, item: Item, buffer: &mut Buffer) -> io::Result<()>;
+}
+
+// 收发同一种消息的编解码器，每个连接持有一份 clone
+pub trait Codec:
+    Decoder<Item: Send + 'static> + Encoder<Self::Item> + Clone + Send + Sync + 'static
+{
+}
+
+impl<C> Codec for C where
+    C: Decoder<Item: Send + 'static> + Encoder<Self::Item> + Clone + Send + Sync + 'static
+{
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// 以 \n 分隔的文本行，解码时去掉行尾的 \r\n 或 \n，编码时追加 \n
+#[derive(Debug, Clone)]
+pub struct LinesCodec {
+    max_length: usize,
+    // 已经检查过不含 \n 的字节数，下次从这里继续查找
+    next_index: usize,
+}
+
+impl LinesCodec {
+    pub fn new() -> Self {
+        Self::with_max_length(usize::MAX)
+    }
+
+    // 超过 max_length 仍没有换行时返回错误，防止对端不发换行占满内存
+    pub fn with_max_length(max_length: usize) -> Self {
+        LinesCodec {
+            max_length,
+            next_index: 0,
+        }
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+
+    fn decode(&mut self, buffer: &mut Buffer) -> io::Result<Option<String>> {
+        let data = buffer.as_slice();
+        let start = self.next_index.min(data.len());
+        let Some(offset) = memchr::memchr(b'\n', &data[start..]) else {
+            if data.len() > self.max_length {
+                return Err(invalid_data("line length limit exceeded"));
+            }
+            self.next_index = data.len();
+            return Ok(None);
+        };
+        let pos = start + offset;
+        self.next_index = 0;
+        let line = match data[..pos].strip_suffix(b"\r") {
+            Some(line) => line,
+            None => &data[..pos],
+        };
+        if line.len() > self.max_length {
+            return Err(invalid_data("line length limit exceeded"));
+        }
+        let line = std::str::from_utf8(line)
+            .map_err(|_| invalid_data("line is not valid utf-8"))?
+            .to_string();
+        buffer.retrieve(pos + 1);
+        Ok(Some(line))
+    }
+}
+
+impl Encoder<String> for LinesCodec {
+    fn encode(&mut self, line: String, buffer: &mut Buffer) -> io::Result<()> {
+        // 行和换行符要么都写入，要么都不写入
+        if line.len() + 1 > buffer.remaining_capacity() {
+            return Err(capacity_exceeded());
+        }
+        buffer.append_string(&line);
+        buffer.append(b"\n");
+        Ok(())
+    }
+}
+
+const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+// 定长的长度头加消息体，长度头只包含消息体的长度
+#[derive(Debug, Clone)]
+pub struct LengthDelimitedCodec {
+    length_field_length: usize,
+    big_endian: bool,
+    max_frame_length: usize,
+}
+
+impl LengthDelimitedCodec {
+    // 默认 4 字节大端长度头，消息体最大 8 MiB
+    pub fn new() -> Self {
+        LengthDelimitedCodec {
+            length_field_length: 4,
+            big_endian: true,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    // 长度头的字节数，只能是 1、2、4 或 8
+    pub fn length_field_length(mut self, length: usize) -> Self {
+        assert!(
+            matches!(length, 1 | 2 | 4 | 8),
+            "length field length must be 1, 2, 4 or 8"
+        );
+        self.length_field_length = length;
+        self
+    }
+
+    pub fn big_endian(mut self) -> Self {
+        self.big_endian = true;
+        self
+    }
+
+    pub fn little_endian(mut self) -> Self {
+        self.big_endian = false;
+        self
+    }
+
+    // 超过上限的帧在解码和编码时都返回错误
+    pub fn max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    fn read_length(&self, header: &[u8]) -> u64 {
+        let mut bytes = [0; 8];
+        if self.big_endian {
+            bytes[8 - header.len()..].copy_from_slice(header);
+            u64::from_be_bytes(bytes)
+        } else {
+            bytes[..header.len()].copy_from_slice(header);
+            u64::from_le_bytes(bytes)
+        }
+    }
+
+    fn write_length(&self, length: u64, buffer: &mut Buffer) {
+        let width = self.length_field_length;
+        if self.big_endian {
+            buffer.append(&length.to_be_bytes()[8 - width..]);
+        } else {
+            buffer.append(&length.to_le_bytes()[..width]);
+        }
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = Bytes;
+
+    fn decode(&mut self, buffer: &mut Buffer) -> io::Result<Option<Bytes>> {
+        let Some(header) = buffer.peek(self.length_field_length) else {
+            return Ok(None);
+        };
+        let length = self.read_length(header);
+        if length > self.max_frame_length as u64 {
+            return Err(invalid_data("frame length limit exceeded"));
+        }
+        // max_frame_length 接近 usize::MAX 时加上头部长度可能溢出
+        let frame_end = usize::try_from(length)
+            .ok()
+            .and_then(|length| length.checked_add(self.length_field_length))
+            .ok_or_else(|| invalid_data("frame length overflows usize"))?;
+        let Some(frame) = buffer.peek(frame_end) else {
+            return Ok(None);
+        };
+        let frame = Bytes::copy_from_slice(&frame[self.length_field_length..]);
+        buffer.retrieve(frame_end);
+        Ok(Some(frame))
+    }
+}
+
+impl Encoder<Bytes> for LengthDelimitedCodec {
+    fn encode(&mut self, frame: Bytes, buffer: &mut Buffer) -> io::Result<()> {
+        let width_limit = match self.length_field_length {
+            8 => u64::MAX,
+            width => (1 << (width * 8)) - 1,
+        };
+        if frame.len() > self.max_frame_length || frame.len() as u64 > width_limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame length limit exceeded",
+            ));
+        }
+        // 头部和消息体要么都写入，要么都不写入
+        if self.length_field_length + frame.len() > buffer.remaining_capacity() {
+            return Err(capacity_exceeded());
+        }
+        self.write_length(frame.len() as u64, buffer);
+        buffer.append(&frame);
+        Ok(())
+    }
+}
+
+// 不分帧，收到多少数据就交出多少
+#[derive(Debug, Clone, Default)]
+pub struct BytesCodec;
+
+impl BytesCodec {
+    pub fn new() -> Self {
+        BytesCodec
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = Bytes;
+
+    fn decode(&mut self, buffer: &mut Buffer) -> io::Result<Option<Bytes>> {
+        if buffer.readable_bytes() == 0 {
+            return Ok(None);
+        }
+        let data = Bytes::copy_from_slice(buffer.as_slice());
+        buffer.retrieve_all();
+        Ok(Some(data))
+    }
+}
+
+impl Encoder<Bytes> for BytesCodec {
+    fn encode(&mut self, data: Bytes, buffer: &mut Buffer) -> io::Result<()> {
+        buffer.try_append(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use bytes::Bytes;
+
+    use super::{BytesCodec, Decoder, Encoder, LengthDelimitedCodec, LinesCodec};
+    use crate::Buffer;
+
+    #[test]
+    fn test_lines_codec() {
+        let mut codec = LinesCodec::with_max_length(8);
+        let mut buffer = Buffer::new();
+        buffer.append(b"hello\r\nwor");
+        assert_eq!(codec.decode(&mut buffer).unwrap().as_deref(), Some("hello"));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        buffer.append(b"ld\n\n");
+        assert_eq!(codec.decode(&mut buffer).unwrap().as_deref(), Some("world"));
+        assert_eq!(codec.decode(&mut buffer).unwrap().as_deref(), Some(""));
+        assert_eq!(buffer.readable_bytes(), 0);
+
+        buffer.append(b"too long line");
+        let err = codec.decode(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut buffer = Buffer::new();
+        codec.encode("ping".to_string(), &mut buffer).unwrap();
+        assert_eq!(buffer.as_slice(), b"ping\n");
+    }
+
+    #[test]
+    fn test_length_delimited_codec() {
+        let mut codec = LengthDelimitedCodec::new();
+        let mut buffer = Buffer::new();
+        codec
+            .encode(Bytes::from_static(b"hello"), &mut buffer)
+            .unwrap();
+        codec.encode(Bytes::new(), &mut buffer).unwrap();
+        assert_eq!(&buffer.as_slice()[..4], &[0, 0, 0, 5]);
+
+        // 逐字节到达时只在帧完整后解出
+        let data = buffer.as_slice().to_vec();
+        let mut input = Buffer::new();
+        let mut frames = Vec::new();
+        for byte in data {
+            input.append(&[byte]);
+            while let Some(frame) = codec.decode(&mut input).unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames, vec![Bytes::from_static(b"hello"), Bytes::new()]);
+
+        let mut codec = LengthDelimitedCodec::new()
+            .length_field_length(2)
+            .little_endian()
+            .max_frame_length(4);
+        let mut buffer = Buffer::new();
+        codec
+            .encode(Bytes::from_static(b"abc"), &mut buffer)
+            .unwrap();
+        assert_eq!(buffer.as_slice(), &[3, 0, b'a', b'b', b'c']);
+        assert!(
+            codec
+                .encode(Bytes::from_static(b"abcde"), &mut buffer)
+                .is_err()
+        );
+
+        buffer.retrieve_all();
+        buffer.append(&[5, 0]);
+        let err = codec.decode(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // 不限制帧长度时，头部加上长度溢出也返回错误而不是 panic
+        let mut unbounded = LengthDelimitedCodec::new()
+            .length_field_length(8)
+            .max_frame_length(usize::MAX);
+        buffer.retrieve_all();
+        buffer.append_u64(u64::MAX);
+        let err = unbounded.decode(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // 超过输出缓冲区上限时不写入头部
+        buffer.retrieve_all();
+        buffer.set_max_capacity(Some(4));
+        let err = codec
+            .encode(Bytes::from_static(b"abc"), &mut buffer)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+        assert_eq!(buffer.readable_bytes(), 0);
+    }
+
+    #[test]
+    fn test_bytes_codec() {
+        let mut codec = BytesCodec::new();
+        let mut buffer = Buffer::new();
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        codec
+            .encode(Bytes::from_static(b"raw"), &mut buffer)
+            .unwrap();
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(Bytes::from_static(b"raw"))
+        );
+        assert_eq!(buffer.readable_bytes(), 0);
+    }
+}
@@ -0,0 +1,230 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use log::warn;
+use mio::net::TcpStream;
+
+use crate::{
+    TcpConnection, TcpOptions,
+    callbacks::{ConnectErrorCallback, ConnectionCallback, MessageCallback},
+    error::Result,
+    socket_sender::SocketSender,
+    timer_queue::TimerId,
+};
+
+// 非阻塞连接：连接注册为 WRITABLE，可写时检查 take_error 和 peer_addr。
+// 成功时以 true 调用 connection_callback，被拒绝或超时时调用 error_callback
+pub struct Connector {
+    addr: SocketAddr,
+    options: TcpOptions,
+    timeout: Option<Duration>,
+    error_callback: ConnectErrorCallback,
+}
+
+impl Connector {
+    pub fn new(addr: SocketAddr) -> Self {
+        Connector {
+            addr,
+            options: TcpOptions::default(),
+            timeout: None,
+            error_callback: Arc::new(default_connect_error_callback),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // 发起连接后、注册到 reactor 之前设置 socket 选项
+    pub fn tcp_options(mut self, options: TcpOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // 超过 timeout 仍未连接成功时以 TimedOut 调用 error_callback
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn error_callback(mut self, callback: ConnectErrorCallback) -> Self {
+        self.error_callback = callback;
+        self
+    }
+
+    // 发起连接，返回的连接注册到 reactor 后等待连接完成。
+    // 立即失败的错误（例如网络不可达）直接返回，不调用 error_callback
+    pub fn connect(
+        self,
+        connection_callback: ConnectionCallback,
+        message_callback: MessageCallback,
+        signal_sender: impl Into<SocketSender<TcpConnection>>,
+    ) -> Result<TcpConnection> {
+        let stream = TcpStream::connect(self.addr)?;
+        self.options.apply(&stream)?;
+        let mut connection = TcpConnection::new(
+            stream,
+            connection_callback,
+            message_callback,
+            mio::Interest::WRITABLE,
+            signal_sender,
+        );
+        connection.set_connecting(Connecting {
+            peer_addr: self.addr,
+            timeout: self.timeout,
+            timer: TimerId::next(),
+            error_callback: self.error_callback,
+        });
+        Ok(connection)
+    }
+}
+
+fn default_connect_error_callback(addr: SocketAddr, error: io::Error) {
+    warn!("Failed to connect {}: {}", addr, error);
+}
+
+// 连接完成前由 TcpConnection 保存
+pub(crate) struct Connecting {
+    pub(crate) peer_addr: SocketAddr,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) timer: TimerId,
+    pub(crate) error_callback: ConnectErrorCallback,
+}
+
+// 可写或出错时检查连接结果，Ok(false) 表示仍在连接中
+pub(crate) fn check_connected(stream: &TcpStream) -> io::Result<bool> {
+    if let Some(e) = stream.take_error()? {
+        return Err(e);
+    }
+    match stream.peer_addr() {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotConnected => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read},
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+            mpsc,
+        },
+        time::Duration,
+    };
+
+    use super::Connector;
+    use crate::{Client, TcpConnection};
+
+    #[test]
+    fn test_connect() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let mut client = Client::<TcpConnection>::connect(
+            Connector::new(addr).timeout(Duration::from_secs(5)),
+            Arc::new(|_, _, _| {}),
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    assert!(conn.write(b"hello"));
+                }
+                tx.lock()
+                    .unwrap()
+                    .send((conn.peer_addr(), is_connected))
+                    .unwrap();
+            }),
+        )
+        .unwrap();
+        // 连接完成前不能写入
+        assert!(!client.write(b"early"));
+        client.listen();
+
+        let (mut peer, _) = listener.accept().unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok((addr, true)));
+        assert_eq!(client.remote().peer_addr(), addr);
+        let mut received = [0; 5];
+        peer.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        peer.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"hello");
+
+        drop(peer);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok((addr, false)));
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_connect_refused() {
+        // 绑定后立即关闭，得到一个没有监听的端口
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let connected = Arc::new(AtomicBool::new(false));
+        let is_connected = connected.clone();
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let mut client = Client::<TcpConnection>::connect(
+            Connector::new(addr).error_callback(Arc::new(move |addr, e| {
+                tx.lock().unwrap().send((addr, e.kind())).unwrap()
+            })),
+            Arc::new(|_, _, _| {}),
+            Arc::new(move |_, _| is_connected.store(true, Ordering::Relaxed)),
+        )
+        .unwrap();
+        client.listen();
+
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Ok((addr, io::ErrorKind::ConnectionRefused))
+        );
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!connected.load(Ordering::Relaxed));
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_connect_timeout() {
+        // backlog 为 0 的监听 socket 只能容纳一个未 accept 的连接，之后的 SYN 被丢弃
+        let socket =
+            socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None).unwrap();
+        socket
+            .bind(
+                &"127.0.0.1:0"
+                    .parse::<std::net::SocketAddr>()
+                    .unwrap()
+                    .into(),
+            )
+            .unwrap();
+        socket.listen(0).unwrap();
+        let addr = socket.local_addr().unwrap().as_socket().unwrap();
+        let _pending: Vec<_> = (0..2)
+            .map(|_| mio::net::TcpStream::connect(addr).unwrap())
+            .collect();
+        std::thread::sleep(Duration::from_millis(20));
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let mut client = Client::<TcpConnection>::connect(
+            Connector::new(addr)
+                .timeout(Duration::from_millis(100))
+                .error_callback(Arc::new(move |_, e| {
+                    tx.lock().unwrap().send(e.kind()).unwrap()
+                })),
+            Arc::new(|_, _, _| {}),
+            Arc::new(|_, _| panic!("should not connect")),
+        )
+        .unwrap();
+        client.listen();
+
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Ok(io::ErrorKind::TimedOut)
+        );
+        assert!(!client.remote().is_established());
+        client.shutdown();
+    }
+}
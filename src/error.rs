@@ -0,0 +1,44 @@
+use std::{fmt, io, net::AddrParseError};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidConfig(String),
+    AddrParse(String, AddrParseError),
+    Io(io::Error),
+    // reactor 的信号队列已满，OverflowPolicy::Reject 时返回
+    QueueFull,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidConfig(msg) => write!(f, "invalid config: {}", msg),
+            Error::AddrParse(addr, e) => write!(f, "invalid address {:?}: {}", addr, e),
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::QueueFull => write!(f, "reactor signal queue is full"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidConfig(_) | Error::QueueFull => None,
+            Error::AddrParse(_, e) => Some(e),
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+pub(crate) fn parse_addr(addr: &str) -> Result<std::net::SocketAddr> {
+    addr.parse()
+        .map_err(|e| Error::AddrParse(addr.to_string(), e))
+}
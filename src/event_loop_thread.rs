@@ -1,4 +1,4 @@
-use crate::{Reactor, ReactorRemote};
+use crate::{Reactor, ReactorRemote, error::Result};
 
 pub struct EventLoopThread<S>
 where
@@ -13,14 +13,8 @@ impl<S> EventLoopThread<S>
 where
     S: crate::ReactorSocket + 'static,
 {
-    pub fn new(sock_capacity: usize) -> Self {
-        let reactor = Reactor::<S>::new(sock_capacity);
-        let reactor_remote = reactor.get_remote();
-        EventLoopThread {
-            thread: None,
-            reactor: Some(reactor),
-            remote: reactor_remote,
-        }
+    pub fn new(sock_capacity: usize) -> Result<Self> {
+        Ok(Self::with_reactor(Reactor::<S>::new(sock_capacity)?))
     }
     pub fn with_reactor(reactor: Reactor<S>) -> Self {
         let reactor_remote = reactor.get_remote();
@@ -1,4 +1,8 @@
-use crate::{EventLoopThread, ReactorRemote};
+use crate::{
+    EventLoopThread, Reactor, ReactorRemote, error::Result, reactor::DEFAULT_EVENTS_CAPACITY,
+};
+
+pub const DEFAULT_SOCK_CAPACITY: usize = 1024;
 
 pub struct EventLoopThreadPool<S>
 where
@@ -12,15 +16,26 @@ impl<S> EventLoopThreadPool<S>
 where
     S: crate::ReactorSocket + 'static,
 {
-    pub fn new(thread_count: usize) -> Self {
+    pub fn new(thread_count: usize) -> Result<Self> {
+        Self::with_capacity(thread_count, DEFAULT_SOCK_CAPACITY, DEFAULT_EVENTS_CAPACITY)
+    }
+
+    pub fn with_capacity(
+        thread_count: usize,
+        sock_capacity: usize,
+        events_capacity: usize,
+    ) -> Result<Self> {
         let mut threads = Vec::with_capacity(thread_count);
         for _ in 0..thread_count {
-            threads.push(EventLoopThread::new(1024));
+            threads.push(EventLoopThread::with_reactor(Reactor::with_capacity(
+                sock_capacity,
+                events_capacity,
+            )?));
         }
-        EventLoopThreadPool {
+        Ok(EventLoopThreadPool {
             threads,
             reactor_index: 0,
-        }
+        })
     }
 
     pub fn reactor_index(&self) -> usize {
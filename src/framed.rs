@@ -0,0 +1,331 @@
+use std::{
+    io,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+use log::warn;
+
+use crate::{
+    Buffer, Client, Server, ServerBuilder, SocketRemote, TcpConnection, TcpOptions,
+    callbacks::{ConnectionCallback, FrameCallback, MessageCallback, default_connection_callback},
+    codec::{Codec, Encoder},
+    error::{Error, Result},
+};
+
+// 保存在连接的 codec_state 中，用户调用 clear_context 不会清除。
+// 解码器只在 loop 线程中使用
+struct FramedDecoder<C>(Mutex<C>);
+
+// send_message 按消息类型取出编码器
+struct FramedEncoder<T>(Mutex<Box<dyn Encoder<T> + Send>>);
+
+impl SocketRemote<TcpConnection> {
+    // 设置 send_message 使用的编码器，分帧层在连接建立时设置
+    pub fn set_encoder<T, E>(&self, encoder: E)
+    where
+        T: 'static,
+        E: Encoder<T> + Send + 'static,
+    {
+        self.set_codec_state(FramedEncoder::<T>(Mutex::new(Box::new(encoder))));
+    }
+
+    // 在调用线程中编码后写入。连接未建立时返回 NotConnected，
+    // 没有 T 类型的编码器时返回 InvalidInput
+    pub fn send_message<T>(&self, item: T) -> Result<()>
+    where
+        T: 'static,
+    {
+        if !self.is_established() {
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
+        }
+        let Some(encoder) = self.codec_state::<FramedEncoder<T>>() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no encoder for this message type",
+            )
+            .into());
+        };
+        let mut buffer = Buffer::new();
+        encoder.0.lock().unwrap().encode(item, &mut buffer)?;
+        self.try_write(buffer.as_slice())
+    }
+}
+
+// 连接建立时为它 clone 一份编解码器，连接关闭时随 SocketRemote 一起释放
+fn framed_connection_callback<C>(
+    codec: C,
+    connection_callback: ConnectionCallback,
+) -> ConnectionCallback
+where
+    C: Codec,
+{
+    Arc::new(move |conn, is_connected| {
+        if is_connected {
+            conn.set_codec_state(FramedDecoder(Mutex::new(codec.clone())));
+            conn.set_encoder::<C::Item, C>(codec.clone());
+        }
+        connection_callback(conn, is_connected);
+    })
+}
+
+// 解出缓冲区中所有完整的消息，解码失败时关闭连接
+fn framed_message_callback<C>(frame_callback: FrameCallback<C::Item>) -> MessageCallback
+where
+    C: Codec,
+{
+    Arc::new(move |conn, buffer, receive_time| {
+        // 没有解码器时输入无法被消费，关闭连接而不是让缓冲区无限增长
+        let Some(decoder) = conn.codec_state::<FramedDecoder<C>>() else {
+            warn!("No decoder for connection {}, close it", conn.peer_addr());
+            buffer.retrieve_all();
+            conn.force_close();
+            return;
+        };
+        loop {
+            // 先释放解码器再调用回调，回调中可以继续 send_message
+            let result = decoder.0.lock().unwrap().decode(buffer);
+            match result {
+                Ok(Some(item)) => frame_callback(conn.clone(), item, receive_time),
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(
+                        "Failed to decode message from {}: {}, close it",
+                        conn.peer_addr(),
+                        e
+                    );
+                    buffer.retrieve_all();
+                    conn.force_close();
+                    break;
+                }
+            }
+        }
+    })
+}
+
+// 回调收到解码后的消息，通过 SocketRemote::send_message 发送消息。
+// 其他选项仍在 ServerBuilder 上设置，但不能再设置 message_callback
+pub struct FramedServer<C>
+where
+    C: Codec,
+{
+    builder: ServerBuilder,
+    codec: C,
+    frame_callback: FrameCallback<C::Item>,
+}
+
+impl<C> FramedServer<C>
+where
+    C: Codec,
+{
+    pub fn new(builder: ServerBuilder, codec: C, frame_callback: FrameCallback<C::Item>) -> Self {
+        FramedServer {
+            builder,
+            codec,
+            frame_callback,
+        }
+    }
+
+    pub fn build(mut self) -> Result<Server> {
+        if self.builder.has_message_callback() {
+            return Err(Error::InvalidConfig(
+                "framed server does not accept a message callback".to_string(),
+            ));
+        }
+        let connection_callback = self
+            .builder
+            .take_connection_callback()
+            .unwrap_or_else(|| Arc::new(default_connection_callback));
+        self.builder
+            .connection_callback(framed_connection_callback(self.codec, connection_callback))
+            .message_callback(framed_message_callback::<C>(self.frame_callback))
+            .build()
+    }
+}
+
+pub struct FramedClient<C>
+where
+    C: Codec,
+{
+    client: Client<TcpConnection>,
+    _codec: PhantomData<fn() -> C>,
+}
+
+impl<C> FramedClient<C>
+where
+    C: Codec,
+{
+    pub fn new(
+        addr: String,
+        codec: C,
+        frame_callback: FrameCallback<C::Item>,
+        connection_callback: ConnectionCallback,
+    ) -> Result<Self> {
+        Self::with_options(
+            addr,
+            TcpOptions::default(),
+            codec,
+            frame_callback,
+            connection_callback,
+        )
+    }
+
+    pub fn with_options(
+        addr: String,
+        options: TcpOptions,
+        codec: C,
+        frame_callback: FrameCallback<C::Item>,
+        connection_callback: ConnectionCallback,
+    ) -> Result<Self> {
+        let client = Client::<TcpConnection>::with_options(
+            addr,
+            options,
+            framed_message_callback::<C>(frame_callback),
+            framed_connection_callback(codec, connection_callback),
+        )?;
+        Ok(FramedClient {
+            client,
+            _codec: PhantomData,
+        })
+    }
+
+    pub fn listen(&mut self) {
+        self.client.listen();
+    }
+
+    pub fn remote(&self) -> &Arc<SocketRemote<TcpConnection>> {
+        self.client.remote()
+    }
+
+    pub fn send_message(&self, item: C::Item) -> Result<()> {
+        self.client.remote().send_message(item)
+    }
+
+    pub fn shutdown(self) {
+        self.client.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        sync::{Arc, Mutex, mpsc},
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+
+    use super::{FramedClient, FramedServer};
+    use crate::{Error, LengthDelimitedCodec, LinesCodec, Server};
+
+    #[test]
+    fn test_lines_echo() {
+        let handle = FramedServer::new(
+            Server::builder().tcp("127.0.0.1:0").io_threads(1),
+            LinesCodec::with_max_length(64),
+            Arc::new(|conn, line: String, _| {
+                conn.send_message(line.to_uppercase()).unwrap();
+            }),
+        )
+        .build()
+        .unwrap()
+        .start()
+        .unwrap();
+        let addr = handle.local_addrs().tcp[0];
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let (conn_tx, conn_rx) = mpsc::channel();
+        let conn_tx = Mutex::new(conn_tx);
+        let mut client = FramedClient::new(
+            addr.to_string(),
+            LinesCodec::new(),
+            Arc::new(move |_, line, _| tx.lock().unwrap().send(line).unwrap()),
+            Arc::new(move |_, is_connected| {
+                conn_tx.lock().unwrap().send(is_connected).unwrap();
+            }),
+        )
+        .unwrap();
+        client.listen();
+        // 连接是异步建立的，建立后才能发送
+        assert_eq!(conn_rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+        client.send_message("hello".to_string()).unwrap();
+        client.send_message("world".to_string()).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "HELLO");
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "WORLD");
+
+        // 清除用户数据不影响分帧层的编解码器
+        client.remote().clear_context();
+        client.send_message("again".to_string()).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "AGAIN");
+
+        // 没有对应类型的编码器
+        assert!(client.remote().send_message(Bytes::new()).is_err());
+        client.remote().force_close();
+        assert_eq!(conn_rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+        // 连接关闭后编码器已释放，仍然返回 NotConnected
+        match client.send_message("late".to_string()) {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotConnected),
+            other => panic!("unexpected result {:?}", other),
+        }
+        client.shutdown();
+
+        // 超长的行违反协议，连接被关闭
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        stream.write_all(&[b'x'; 100]).unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        assert!(received.is_empty());
+
+        handle.shutdown();
+        handle.join();
+    }
+
+    #[test]
+    fn test_length_delimited() {
+        let handle = FramedServer::new(
+            Server::builder().tcp("127.0.0.1:0").io_threads(1),
+            LengthDelimitedCodec::new(),
+            Arc::new(|conn, frame: Bytes, _| {
+                conn.send_message(frame).unwrap();
+            }),
+        )
+        .build()
+        .unwrap()
+        .start()
+        .unwrap();
+        let addr = handle.local_addrs().tcp[0];
+
+        // 一个帧分两次写入，另一个帧紧随其后
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        stream.write_all(&[0, 0, 0, 3, b'a']).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        stream.write_all(&[b'b', b'c', 0, 0, 0, 1, b'd']).unwrap();
+        let mut received = [0; 12];
+        stream.read_exact(&mut received).unwrap();
+        assert_eq!(&received, &[0, 0, 0, 3, b'a', b'b', b'c', 0, 0, 0, 1, b'd']);
+
+        handle.shutdown();
+        handle.join();
+    }
+
+    #[test]
+    fn test_message_callback_conflict() {
+        let result = FramedServer::new(
+            Server::builder()
+                .tcp("127.0.0.1:0")
+                .message_callback(Arc::new(|_, _, _| {})),
+            LinesCodec::new(),
+            Arc::new(|_, _, _| {}),
+        )
+        .build();
+        assert!(matches!(result, Err(Error::InvalidConfig(_))));
+    }
+}
@@ -1,15 +1,21 @@
+pub mod error;
+pub use error::{Error, Result};
+
 pub mod reactor_socket;
 pub use reactor_socket::ReactorSocket;
 
 pub mod reactor;
-pub use reactor::Reactor;
+pub use reactor::{LoopId, Reactor};
 
 pub mod server;
-pub use server::Server;
+pub use server::{LocalAddrs, Server, ServerBuilder, ServerHandle};
 
 pub mod acceptor;
 pub use acceptor::Acceptor;
 
+pub mod any_socket;
+pub use any_socket::{AnySocket, CustomSocket};
+
 pub mod callbacks;
 
 pub mod event_loop_thread;
@@ -21,6 +27,9 @@ pub use event_loop_thread_pool::EventLoopThreadPool;
 pub mod tcp_connection;
 pub use tcp_connection::TcpConnection;
 
+pub mod tcp_options;
+pub use tcp_options::{Keepalive, TcpOptions};
+
 pub mod socket_remote;
 pub use socket_remote::SocketRemote;
 
@@ -30,10 +39,30 @@ pub use udp_socket::UdpSocket;
 pub mod buffer;
 pub use buffer::Buffer;
 
+pub mod output_queue;
+
+pub mod codec;
+pub use codec::{BytesCodec, Codec, Decoder, Encoder, LengthDelimitedCodec, LinesCodec};
+
+pub mod framed;
+pub use framed::{FramedClient, FramedServer};
+
+pub mod mpsc_queue;
+
 pub mod reactor_channel;
+pub use reactor_channel::{OverflowPolicy, SignalStats};
+
+pub mod socket_sender;
+pub use socket_sender::SocketSender;
 
 pub mod reactor_remote;
 pub use reactor_remote::ReactorRemote;
 
 pub mod client;
 pub use client::Client;
+
+pub mod connector;
+pub use connector::Connector;
+
+pub mod timer_queue;
+pub use timer_queue::TimerId;
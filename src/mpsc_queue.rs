@@ -0,0 +1,145 @@
+use std::{
+    cell::UnsafeCell,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+struct Node<T> {
+    next: AtomicPtr<Node<T>>,
+    value: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: Option<T>) -> *mut Self {
+        Box::into_raw(Box::new(Node {
+            next: AtomicPtr::new(ptr::null_mut()),
+            value,
+        }))
+    }
+}
+
+// Vyukov 的无锁多生产者单消费者队列。
+// push 只有一次原子交换，可以在任意线程调用；pop 只能由唯一的消费者调用
+pub struct MpscQueue<T> {
+    // 生产者在 head 端追加
+    head: AtomicPtr<Node<T>>,
+    // 消费者在 tail 端取出，tail 指向已取出的哨兵节点
+    tail: UnsafeCell<*mut Node<T>>,
+}
+
+unsafe impl<T: Send> Send for MpscQueue<T> {}
+unsafe impl<T: Send> Sync for MpscQueue<T> {}
+
+impl<T> MpscQueue<T> {
+    pub fn new() -> Self {
+        let stub = Node::new(None);
+        MpscQueue {
+            head: AtomicPtr::new(stub),
+            tail: UnsafeCell::new(stub),
+        }
+    }
+
+    pub fn push(&self, value: T) {
+        let node = Node::new(Some(value));
+        let prev = self.head.swap(node, Ordering::AcqRel);
+        // 在 swap 和 store 之间，消费者会暂时看不到这个节点，当作队列为空处理
+        unsafe { (*prev).next.store(node, Ordering::Release) };
+    }
+
+    /// # Safety
+    /// 同一时刻只能有一个线程调用 pop
+    pub unsafe fn pop(&self) -> Option<T> {
+        unsafe {
+            let tail = *self.tail.get();
+            let next = (*tail).next.load(Ordering::Acquire);
+            if next.is_null() {
+                return None;
+            }
+            *self.tail.get() = next;
+            let value = (*next).value.take();
+            drop(Box::from_raw(tail));
+            value
+        }
+    }
+
+    /// # Safety
+    /// 不能与 pop 同时调用
+    pub unsafe fn is_empty(&self) -> bool {
+        unsafe { (**self.tail.get()).next.load(Ordering::Acquire).is_null() }
+    }
+}
+
+impl<T> Default for MpscQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for MpscQueue<T> {
+    fn drop(&mut self) {
+        unsafe {
+            while self.pop().is_some() {}
+            drop(Box::from_raw(*self.tail.get()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::MpscQueue;
+
+    #[test]
+    fn test_fifo() {
+        let queue = MpscQueue::new();
+        assert!(unsafe { queue.is_empty() });
+        for i in 0..3 {
+            queue.push(i);
+        }
+        assert!(!unsafe { queue.is_empty() });
+        assert_eq!(unsafe { queue.pop() }, Some(0));
+        assert_eq!(unsafe { queue.pop() }, Some(1));
+        queue.push(3);
+        assert_eq!(unsafe { queue.pop() }, Some(2));
+        assert_eq!(unsafe { queue.pop() }, Some(3));
+        assert_eq!(unsafe { queue.pop() }, None);
+
+        // 未取出的元素在 drop 时释放
+        let queue = MpscQueue::new();
+        let value = Arc::new(());
+        queue.push(value.clone());
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_multi_producer() {
+        let queue = Arc::new(MpscQueue::new());
+        let producers: Vec<_> = (0..4)
+            .map(|p| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for i in 0..10000 {
+                        queue.push((p, i));
+                    }
+                })
+            })
+            .collect();
+
+        let mut next = [0; 4];
+        let mut received = 0;
+        while received < 40000 {
+            if let Some((p, i)) = unsafe { queue.pop() } {
+                // 同一个生产者的元素保持顺序
+                assert_eq!(next[p], i);
+                next[p] += 1;
+                received += 1;
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!(unsafe { queue.pop() }, None);
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, IoSlice, Write},
+    os::fd::AsRawFd,
+    sync::Arc,
+};
+
+use bytes::{Buf, Bytes};
+
+// 单次 write_vectored 最多提交的数据块数，不超过 Linux 的 IOV_MAX
+pub const MAX_IOVECS: usize = 64;
+
+// Linux 上单次 sendfile 最多传输的字节数
+const MAX_SENDFILE: u64 = 0x7fff_f000;
+
+// 文件中待发送的一段区间，由 sendfile 直接从文件写入 socket，不经过用户态。
+// 多个连接可以共享同一个 Arc<File>，读取位置由 offset 指定，不影响文件本身的偏移
+#[derive(Debug, Clone)]
+pub struct FileRegion {
+    file: Arc<File>,
+    offset: u64,
+    len: u64,
+}
+
+impl FileRegion {
+    pub fn new(file: impl Into<Arc<File>>, offset: u64, len: u64) -> Self {
+        FileRegion {
+            file: file.into(),
+            offset,
+            len,
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    // 尚未发送的字节数
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_to<W: Write + AsRawFd>(&self, writer: &mut W) -> io::Result<usize> {
+        let count = self.len.min(MAX_SENDFILE) as usize;
+        loop {
+            let mut offset = self.offset as libc::off_t;
+            let n = unsafe {
+                libc::sendfile(
+                    writer.as_raw_fd(),
+                    self.file.as_raw_fd(),
+                    &mut offset,
+                    count,
+                )
+            };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    // 没有 sendfile 的平台先读到栈上再写出
+    #[cfg(not(target_os = "linux"))]
+    fn send_to<W: Write + AsRawFd>(&self, writer: &mut W) -> io::Result<usize> {
+        use std::os::unix::fs::FileExt;
+
+        let mut buf = [0; 64 * 1024];
+        let count = self.len.min(buf.len() as u64) as usize;
+        let n = self.file.read_at(&mut buf[..count], self.offset)?;
+        if n == 0 {
+            return Ok(0);
+        }
+        writer.write(&buf[..n])
+    }
+}
+
+#[derive(Debug)]
+enum Segment {
+    Bytes(Bytes),
+    File(FileRegion),
+}
+
+// 待发送的数据块队列。用户交给 write_bytes 的 Bytes 直接入队，不再复制；
+// 文件区间与数据块按入队顺序发送
+#[derive(Debug, Default)]
+pub struct OutputQueue {
+    segments: VecDeque<Segment>,
+    len: usize,
+}
+
+impl OutputQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: Bytes) {
+        if !chunk.is_empty() {
+            self.len += chunk.len();
+            self.segments.push_back(Segment::Bytes(chunk));
+        }
+    }
+
+    pub fn push_file(&mut self, region: FileRegion) {
+        if !region.is_empty() {
+            self.len += region.len as usize;
+            self.segments.push_back(Segment::File(region));
+        }
+    }
+
+    // 释放队列本身占用的多余空间
+    pub fn shrink_to_fit(&mut self) {
+        self.segments.shrink_to_fit();
+    }
+
+    // 待发送的总字节数，包括文件区间
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // 把队首连续的若干数据块交给 write 做一次向量写，并去掉已写出的部分。
+    // 队首是文件区间时 write 收到空的 slice，应改用 write_to
+    pub fn write_with<F>(&mut self, write: F) -> io::Result<usize>
+    where
+        F: FnOnce(&[IoSlice<'_>]) -> io::Result<usize>,
+    {
+        let mut slices = [IoSlice::new(&[]); MAX_IOVECS];
+        let mut count = 0;
+        for segment in self.segments.iter().take(MAX_IOVECS) {
+            let Segment::Bytes(chunk) = segment else {
+                break;
+            };
+            slices[count] = IoSlice::new(chunk);
+            count += 1;
+        }
+        let written = write(&slices[..count])?;
+        self.advance(written);
+        Ok(written)
+    }
+
+    // 写一次到 socket：队首是文件区间时调用 sendfile，否则向量写队首的数据块。
+    // 文件在发送期间被截断时返回 UnexpectedEof
+    pub fn write_to<W: Write + AsRawFd>(&mut self, writer: &mut W) -> io::Result<usize> {
+        let Some(Segment::File(region)) = self.segments.front_mut() else {
+            return self.write_with(|bufs| writer.write_vectored(bufs));
+        };
+        let written = region.send_to(writer)?;
+        if written == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file is shorter than the region to send",
+            ));
+        }
+        region.offset += written as u64;
+        region.len -= written as u64;
+        self.len -= written;
+        if region.is_empty() {
+            self.segments.pop_front();
+        }
+        Ok(written)
+    }
+
+    fn advance(&mut self, mut n: usize) {
+        self.len -= n;
+        while n > 0 {
+            let Some(Segment::Bytes(front)) = self.segments.front_mut() else {
+                unreachable!("advance past byte chunks");
+            };
+            if n < front.len() {
+                front.advance(n);
+                return;
+            }
+            n -= front.len();
+            self.segments.pop_front();
+        }
+    }
+
+    // 取出全部数据块。只在 crate 内部用于由写入信号构造、没有文件区间的队列
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = Bytes> + '_ {
+        self.len = 0;
+        self.segments.drain(..).map(|segment| match segment {
+            Segment::Bytes(chunk) => chunk,
+            Segment::File(_) => panic!("cannot drain file regions as bytes"),
+        })
+    }
+}
+
+impl FromIterator<Bytes> for OutputQueue {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+        let mut queue = OutputQueue::new();
+        iter.into_iter().for_each(|chunk| queue.push(chunk));
+        queue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+    };
+
+    use bytes::Bytes;
+
+    use super::{FileRegion, MAX_IOVECS, OutputQueue};
+
+    #[test]
+    fn test_partial_write() {
+        let mut queue: OutputQueue = [&b"ab"[..], b"", b"cde", b"f"]
+            .into_iter()
+            .map(Bytes::from_static)
+            .collect();
+        assert_eq!(queue.len(), 6);
+
+        // 每次最多写 4 个字节，跨越块的边界
+        let mut output = Vec::new();
+        while !queue.is_empty() {
+            let n = queue
+                .write_with(|bufs| {
+                    let limited: Vec<u8> = bufs
+                        .iter()
+                        .flat_map(|b| b.iter())
+                        .take(4)
+                        .copied()
+                        .collect();
+                    output.extend_from_slice(&limited);
+                    Ok(limited.len())
+                })
+                .unwrap();
+            assert!(n > 0);
+        }
+        assert_eq!(output, b"abcdef");
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn test_iovec_limit() {
+        let mut queue: OutputQueue = (0..MAX_IOVECS + 10)
+            .map(|_| Bytes::from_static(b"x"))
+            .collect();
+        let n = queue.write_with(|bufs| Ok(bufs.len())).unwrap();
+        assert_eq!(n, MAX_IOVECS);
+        assert_eq!(queue.len(), 10);
+        assert_eq!(queue.drain().count(), 10);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_file_region() {
+        let path = std::env::temp_dir().join(format!("output_queue_{}", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(b"0123456789")
+            .unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut queue = OutputQueue::new();
+        queue.push(Bytes::from_static(b"<"));
+        queue.push_file(FileRegion::new(file, 2, 5));
+        queue.push(Bytes::from_static(b">"));
+        assert_eq!(queue.len(), 7);
+
+        let (mut writer, mut reader) = UnixStream::pair().unwrap();
+        while !queue.is_empty() {
+            assert!(queue.write_to(&mut writer).unwrap() > 0);
+        }
+        drop(writer);
+        let mut received = Vec::new();
+        reader.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"<23456>");
+    }
+
+    #[test]
+    fn test_truncated_file_region() {
+        let path = std::env::temp_dir().join(format!("output_queue_eof_{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut queue = OutputQueue::new();
+        queue.push_file(FileRegion::new(file, 0, 8));
+        let (mut writer, _reader) = UnixStream::pair().unwrap();
+        assert_eq!(queue.write_to(&mut writer).unwrap(), 3);
+        let err = queue.write_to(&mut writer).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,26 +1,92 @@
 use std::{
+    any::Any,
+    cell::{Cell, RefCell},
     net::SocketAddr,
     sync::{
-        Arc, Mutex,
+        Arc,
         atomic::{AtomicU64, Ordering},
     },
-    thread::ThreadId,
-    usize,
+    time::{Duration, Instant},
 };
 
+use bytes::Bytes;
 use log::{error, info, trace, warn};
 use mio::{Events, Poll, Token, Waker};
 use slab::Slab;
 
 use crate::{
     ReactorRemote,
+    error::Result,
+    output_queue::OutputQueue,
     reactor_channel::{Receiver, Sender},
+    timer_queue::{TimerCallback, TimerId, TimerQueue},
 };
 
-pub fn u64_current_thread_id() -> u64 {
-    unsafe { std::mem::transmute::<ThreadId, u64>(std::thread::current().id()) }
+// reactor 的唯一标识。reactor 运行期间，所在线程的 thread-local 记录它的 LoopId，
+// 据此判断调用方是否处于该 reactor 的 loop 线程
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LoopId(u64);
+
+static NEXT_LOOP_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    // 0 表示当前线程没有运行 reactor
+    static CURRENT_LOOP: Cell<u64> = const { Cell::new(0) };
+    // 当前线程运行的 reactor 的 ReactorRemote<S>，擦除了类型
+    static CURRENT_REMOTE: RefCell<Option<Box<dyn Any>>> = const { RefCell::new(None) };
+}
+
+impl LoopId {
+    pub fn next() -> Self {
+        LoopId(NEXT_LOOP_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    // 当前线程正在运行的 reactor
+    pub fn current() -> Option<Self> {
+        match CURRENT_LOOP.get() {
+            0 => None,
+            id => Some(LoopId(id)),
+        }
+    }
+
+    pub fn is_current(self) -> bool {
+        CURRENT_LOOP.get() == self.0
+    }
+}
+
+// run 期间把 reactor 登记到当前线程，退出（包括 panic）时清除
+struct LoopGuard;
+
+impl LoopGuard {
+    fn enter(id: LoopId, remote: Box<dyn Any>) -> Self {
+        assert!(
+            LoopId::current().is_none(),
+            "another reactor is already running on this thread"
+        );
+        CURRENT_LOOP.set(id.0);
+        CURRENT_REMOTE.set(Some(remote));
+        LoopGuard
+    }
 }
 
+impl Drop for LoopGuard {
+    fn drop(&mut self) {
+        CURRENT_LOOP.set(0);
+        drop(CURRENT_REMOTE.take());
+    }
+}
+
+// Token 低位为 slab 下标，高位为该槽位的代数。
+// 槽位被复用时代数加一，旧 SocketRemote 持有的 Token 随之失效
+const INDEX_BITS: u32 = usize::BITS / 2;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+// 代数不会取到全 1，避免与 waker 的 Token(usize::MAX) 冲突
+const GENERATION_LIMIT: usize = usize::MAX >> INDEX_BITS;
+
+pub const DEFAULT_EVENTS_CAPACITY: usize = 1024;
+
+pub type Functor<S> = Box<dyn FnOnce(&mut Reactor<S>) + Send>;
+
 pub enum ReactorSignal<S>
 where
     S: crate::ReactorSocket,
@@ -28,9 +94,15 @@ where
     Quit,
     Register(S),
     ShutDown(Token),
+    ForceClose(Token),
     ReRegister(Token, mio::Interest),
-    Write(Token, Vec<u8>),
+    // 按 socket 处理信号时的 interest 重新注册，排队期间 interest 的变化不会被旧值覆盖
+    SyncInterest(Token),
+    Write(Token, Vec<Bytes>),
     Send(Token, SocketAddr, Vec<u8>), // For UDP sockets
+    AddTimer(TimerId, Instant, TimerCallback),
+    CancelTimer(TimerId),
+    Functor(Functor<S>),
 }
 
 impl<S> ReactorSignal<S>
@@ -42,9 +114,14 @@ where
             Self::Quit => "Quit",
             Self::Register(_) => "Register",
             Self::ShutDown(_) => "ShutDown",
+            Self::ForceClose(_) => "ForceClose",
             Self::ReRegister(_, _) => "ReRegister",
+            Self::SyncInterest(_) => "SyncInterest",
             Self::Write(_, _) => "Write",
             Self::Send(_, _, _) => "DatagramSend",
+            Self::AddTimer(_, _, _) => "AddTimer",
+            Self::CancelTimer(_) => "CancelTimer",
+            Self::Functor(_) => "Functor",
         }
     }
 }
@@ -56,28 +133,39 @@ where
     poll: Poll,
     events: mio::Events,
     sockets: Slab<S>,
+    generations: Vec<usize>,
+    timers: TimerQueue,
     signal_receiver: Receiver<ReactorSignal<S>>,
     quit: bool,
+    // 排空的截止时间，Some 表示正在排空
+    drain_deadline: Option<Instant>,
     waker: Arc<Waker>,
-    thread_id: Arc<AtomicU64>,
+    loop_id: LoopId,
 }
 
 impl<S> Reactor<S>
 where
     S: crate::ReactorSocket,
 {
-    pub fn new(sock_capacity: usize) -> Self {
-        let poll = Poll::new().unwrap();
-        let waker = Arc::new(Waker::new(poll.registry(), Token(usize::MAX)).unwrap());
-        Reactor {
+    pub fn new(sock_capacity: usize) -> Result<Self> {
+        Self::with_capacity(sock_capacity, DEFAULT_EVENTS_CAPACITY)
+    }
+
+    pub fn with_capacity(sock_capacity: usize, events_capacity: usize) -> Result<Self> {
+        let poll = Poll::new()?;
+        let waker = Arc::new(Waker::new(poll.registry(), Token(usize::MAX))?);
+        Ok(Reactor {
             poll,
-            events: Events::with_capacity(1024),
+            events: Events::with_capacity(events_capacity),
             sockets: Slab::with_capacity(sock_capacity),
-            signal_receiver: Receiver::new(Arc::new(Mutex::new(Vec::new()))),
+            generations: Vec::with_capacity(sock_capacity),
+            timers: TimerQueue::new(),
+            signal_receiver: Receiver::new(),
             quit: false,
+            drain_deadline: None,
             waker,
-            thread_id: Arc::new(AtomicU64::new(u64::MAX / 2)),
-        }
+            loop_id: LoopId::next(),
+        })
     }
 
     pub fn get_remote(&self) -> ReactorRemote<S> {
@@ -85,34 +173,81 @@ where
     }
 
     pub fn get_sender(&self) -> Sender<ReactorSignal<S>> {
-        Sender::new(
-            self.signal_receiver.queue.clone(),
-            self.waker.clone(),
-            self.thread_id.clone(),
-        )
+        self.signal_receiver
+            .sender(self.waker.clone(), self.loop_id)
+    }
+
+    pub fn loop_id(&self) -> LoopId {
+        self.loop_id
+    }
+
+    // 持有 reactor 时已在 loop 线程中，functor 立即执行
+    pub fn run_in_loop<F>(&mut self, functor: F)
+    where
+        F: FnOnce(&mut Reactor<S>),
+    {
+        functor(self);
     }
 
-    pub fn run(mut self) {
-        self.thread_id
-            .store(u64_current_thread_id(), Ordering::Relaxed);
+    // 当前线程正在运行的 reactor，供回调中的代码使用。
+    // 不在 loop 线程中，或运行的 reactor 承载的不是 S 时返回 None
+    pub fn current() -> Option<ReactorRemote<S>>
+    where
+        S: 'static,
+    {
+        CURRENT_REMOTE
+            .with_borrow(|remote| remote.as_ref()?.downcast_ref::<ReactorRemote<S>>().cloned())
+    }
+
+    pub fn run(mut self)
+    where
+        S: 'static,
+    {
+        let _guard = LoopGuard::enter(self.loop_id, Box::new(self.get_remote()));
         // 运行事件循环
         while !self.quit {
+            // 以最近的定时器到期时间作为 poll 的超时
+            // loop 线程自己投递的信号不会唤醒 poll，有积压时不能阻塞
+            let now = Instant::now();
+            let timeout = if self.signal_receiver.is_empty() {
+                let drain_timeout = self
+                    .drain_deadline
+                    .map(|deadline| deadline.saturating_duration_since(now));
+                match (self.timers.next_timeout(now), drain_timeout) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                }
+            } else {
+                Some(Duration::ZERO)
+            };
             self.poll
-                .poll(&mut self.events, None)
+                .poll(&mut self.events, timeout)
                 .expect("Failed to poll events");
-            let receive_time = std::time::Instant::now();
+            let receive_time = Instant::now();
 
             for event in self.events.iter() {
                 trace!("reveice event with token({})", event.token().0);
-                if let Some(socket) = self.sockets.get_mut(event.token().0) {
-                    socket.handle_event(event, receive_time);
+                if let Some(index) = self.index_of(event.token()) {
+                    self.sockets[index].handle_event(event, receive_time);
                 }
             }
 
+            self.timers.run_expired(Instant::now());
+
             let signals = self.signal_receiver.take_all();
             for signal in signals {
                 self.handle_signal(signal);
             }
+
+            // 排空期间所有 socket 都已关闭或到达截止时间时退出
+            if let Some(deadline) = self.drain_deadline {
+                if !self.sockets.is_empty() && Instant::now() >= deadline {
+                    self.close_all();
+                }
+                if self.sockets.is_empty() {
+                    self.quit();
+                }
+            }
         }
         info!("Reactor has quit");
     }
@@ -121,14 +256,30 @@ where
         trace!("handle signal: {}", signal.type_str());
         match signal {
             ReactorSignal::Quit => self.quit(),
-            ReactorSignal::Register(socket) => {
-                self.register(socket);
-                ()
-            }
+            ReactorSignal::Register(socket) => match self.register(socket) {
+                // 排空开始后才到达的连接同样优雅关闭
+                Ok(token) if self.is_draining() => self.shutdown(token),
+                Ok(_) => {}
+                Err(e) => error!("Failed to register socket: {}", e),
+            },
             ReactorSignal::ShutDown(token) => self.shutdown(token),
+            ReactorSignal::ForceClose(token) => self.close(token),
             ReactorSignal::ReRegister(token, interest) => self.reregister(token, interest),
+            ReactorSignal::SyncInterest(token) => {
+                if let Some(index) = self.checked_index(token, "sync interest") {
+                    let interest = self.sockets[index].interest();
+                    self.reregister(token, interest);
+                }
+            }
             ReactorSignal::Write(token, data) => self.write(token, data),
             ReactorSignal::Send(token, addr, data) => self.send(token, addr, data),
+            ReactorSignal::AddTimer(id, when, callback) => self.timers.add(id, when, callback),
+            ReactorSignal::CancelTimer(id) => {
+                if !self.timers.cancel(id) {
+                    trace!("timer {:?} already expired or cancelled", id);
+                }
+            }
+            ReactorSignal::Functor(functor) => functor(self),
         }
     }
 
@@ -137,43 +288,170 @@ where
         self.quit = true;
     }
 
-    pub fn register(&mut self, socket: S) -> Option<Token> {
+    // 优雅关闭所有 socket，超过 timeout 后强制关闭剩余的并退出
+    pub fn drain(&mut self, timeout: Duration) {
+        if self.is_draining() {
+            return;
+        }
+        info!(
+            "Reactor is draining {} sockets, timeout {:?}",
+            self.sockets.len(),
+            timeout
+        );
+        self.drain_deadline = Some(Instant::now() + timeout);
+        for token in self.tokens() {
+            self.shutdown(token);
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.drain_deadline.is_some()
+    }
+
+    // 立即关闭所有 socket，每个 socket 都会收到 handle_establish(false)
+    pub fn close_all(&mut self) {
+        if !self.sockets.is_empty() {
+            warn!("Force close {} sockets", self.sockets.len());
+        }
+        for token in self.tokens() {
+            self.close(token);
+        }
+    }
+
+    fn tokens(&self) -> Vec<Token> {
+        self.sockets
+            .iter()
+            .map(|(index, _)| Token((self.generations[index] << INDEX_BITS) | index))
+            .collect()
+    }
+
+    pub fn socket(&self, token: Token) -> Option<&S> {
+        self.index_of(token).map(|index| &self.sockets[index])
+    }
+
+    pub fn socket_mut(&mut self, token: Token) -> Option<&mut S> {
+        self.index_of(token).map(|index| &mut self.sockets[index])
+    }
+
+    // 校验 Token 的代数，槽位已被复用或已释放时返回 None
+    fn index_of(&self, token: Token) -> Option<usize> {
+        let index = token.0 & INDEX_MASK;
+        match self.generations.get(index) {
+            Some(&generation)
+                if generation == token.0 >> INDEX_BITS && self.sockets.contains(index) =>
+            {
+                Some(index)
+            }
+            _ => None,
+        }
+    }
+
+    // 查找 Token 对应的槽位，失效的 Token 会被拒绝并记录
+    fn checked_index(&self, token: Token, operation: &str) -> Option<usize> {
+        let index = self.index_of(token);
+        if index.is_none() {
+            warn!(
+                "reject {} on stale token: Token({:#x}), slot {} generation {}",
+                operation,
+                token.0,
+                token.0 & INDEX_MASK,
+                token.0 >> INDEX_BITS
+            );
+        }
+        index
+    }
+
+    pub fn run_after<F>(&mut self, delay: Duration, callback: F) -> TimerId
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let id = TimerId::next();
+        self.timers.add(
+            id,
+            Instant::now() + delay,
+            TimerCallback::Once(Box::new(callback)),
+        );
+        id
+    }
+
+    pub fn run_every<F>(&mut self, interval: Duration, callback: F) -> TimerId
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let id = TimerId::next();
+        self.timers.add(
+            id,
+            Instant::now() + interval,
+            TimerCallback::Repeat(Box::new(callback), interval),
+        );
+        id
+    }
+
+    pub fn cancel(&mut self, timer_id: TimerId) -> bool {
+        self.timers.cancel(timer_id)
+    }
+
+    pub fn register(&mut self, socket: S) -> Result<Token> {
         let interest = socket.interest();
-        let token = Token(self.sockets.insert(socket));
-        if self
+        let index = self.sockets.vacant_key();
+        if index >= INDEX_MASK {
+            return Err(std::io::Error::other("too many sockets in reactor").into());
+        }
+        if index >= self.generations.len() {
+            self.generations.resize(index + 1, 0);
+        }
+        let token = Token((self.generations[index] << INDEX_BITS) | index);
+        self.sockets.insert(socket);
+        if let Err(e) = self
             .poll
             .registry()
-            .register(self.sockets[token.0].socket(), token, interest)
-            .is_err()
+            .register(self.sockets[index].socket(), token, interest)
         {
-            self.sockets.remove(token.0);
-            error!("Failed to register socket");
-            return None;
+            self.remove(index);
+            return Err(e.into());
         }
-        self.sockets[token.0].set_poll_token(token);
-        self.sockets[token.0].handle_establish(true);
-        Some(token)
+        self.sockets[index].set_poll_token(token);
+        self.sockets[index].handle_establish(true);
+        Ok(token)
     }
 
+    fn remove(&mut self, index: usize) -> S {
+        self.generations[index] = (self.generations[index] + 1) % GENERATION_LIMIT;
+        self.sockets.remove(index)
+    }
+
+    // 优雅关闭，由 socket 决定是否需要等待输出缓冲区发送完毕
     fn shutdown(&mut self, token: Token) {
-        if !self.sockets.contains(token.0) {
-            error!("no such token to shutdown: Token({})", token.0);
+        let Some(index) = self.checked_index(token, "shutdown") else {
             return;
+        };
+        if self.sockets[index].handle_shutdown() {
+            self.close(token);
         }
+    }
+
+    // 立即注销并释放 socket
+    pub(crate) fn close(&mut self, token: Token) {
+        let Some(index) = self.checked_index(token, "close") else {
+            return;
+        };
         if self
             .poll
             .registry()
-            .deregister(self.sockets[token.0].socket())
+            .deregister(self.sockets[index].socket())
             .is_err()
         {
             error!("Failed to deregister socket");
         }
-        self.sockets[token.0].handle_establish(false);
-        self.sockets.remove(token.0);
+        self.sockets[index].handle_establish(false);
+        self.remove(index);
     }
 
     fn reregister(&mut self, token: Token, interest: mio::Interest) {
-        let socket = &mut self.sockets[token.0];
+        let Some(index) = self.checked_index(token, "reregister") else {
+            return;
+        };
+        let socket = &mut self.sockets[index];
         socket.set_interest(interest);
         if self
             .poll
@@ -185,53 +463,58 @@ where
         }
     }
 
-    fn write(&mut self, token: Token, data: Vec<u8>) {
-        if let Some(socket) = self.sockets.get_mut(token.0) {
+    fn write(&mut self, token: Token, chunks: Vec<Bytes>) {
+        if let Some(index) = self.checked_index(token, "write") {
+            let socket = &mut self.sockets[index];
+            let mut output: OutputQueue = chunks.into_iter().collect();
+            if socket.is_disconnecting() {
+                warn!(
+                    "Drop {} bytes written after shutdown: {:?}",
+                    output.len(),
+                    token
+                );
+                return;
+            }
             if !socket.interest().is_writable() {
-                let mut total_written = 0;
-                loop {
-                    match socket.write(data[total_written..].as_ref()) {
-                        Ok(bytes_written) if bytes_written == 0 => {
+                while !output.is_empty() {
+                    match output.write_with(|bufs| socket.write_vectored(bufs)) {
+                        Ok(0) => {
                             error!(
                                 "Connection closed while writing to socket with token {:?}",
                                 token
                             );
-                            self.shutdown(token);
+                            self.close(token);
                             return;
                         }
-                        Ok(bytes_written) => {
-                            total_written += bytes_written;
-                            if total_written == data.len() {
-                                break;
-                            }
-                        }
+                        Ok(_) => {}
                         Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                             trace!("Socket would block on write");
                             break;
                         }
                         Err(e) => {
                             error!("Failed to write to socket: {}", e);
-                            self.shutdown(token);
+                            self.close(token);
                             return;
                         }
                     }
                 }
-                if total_written < data.len() {
-                    socket.stash_output(data[total_written..].as_ref());
+                if !output.is_empty() {
+                    output.drain().for_each(|chunk| socket.stash_chunk(chunk));
                     let interest = socket.interest().add(mio::Interest::WRITABLE);
                     self.reregister(token, interest);
+                } else {
+                    socket.handle_write_complete();
                 }
             } else {
-                socket.stash_output(data[..].as_ref());
+                output.drain().for_each(|chunk| socket.stash_chunk(chunk));
             }
-        } else {
-            error!("Socket with token {:?} not found", token);
         }
     }
 
     // Only call on UdpSocket
     fn send(&mut self, token: Token, addr: SocketAddr, data: Vec<u8>) {
-        if let Some(socket) = self.sockets.get_mut(token.0) {
+        if let Some(index) = self.checked_index(token, "send") {
+            let socket = &mut self.sockets[index];
             match socket.send(addr, data.as_ref()) {
                 Ok(bytes_sent) => {
                     trace!("Sent {} bytes to {}", bytes_sent, addr);
@@ -242,11 +525,135 @@ where
                 Err(e) => {
                     error!("Failed to send data: {}", e);
                     // TODO: solve some error to shutdown
-                    // self.shutdown(token);
+                    // self.close(token);
                 }
             }
-        } else {
-            error!("Socket with token {:?} not found", token);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex, mpsc},
+        time::Duration,
+    };
+
+    use crate::{EventLoopThread, LoopId, Reactor, TcpConnection, UdpSocket};
+
+    fn udp_socket(reactor: &Reactor<UdpSocket>) -> UdpSocket {
+        let socket = mio::net::UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        UdpSocket::new(socket, Arc::new(|_, _, _, _| {}), reactor.get_sender())
+    }
+
+    #[test]
+    fn test_stale_token_rejected() {
+        let mut reactor = Reactor::<UdpSocket>::new(2).unwrap();
+        let first = reactor.register(udp_socket(&reactor)).unwrap();
+        reactor.close(first);
+        assert!(reactor.socket(first).is_none());
+
+        let second = reactor.register(udp_socket(&reactor)).unwrap();
+        assert_ne!(first, second);
+        assert!(reactor.socket(first).is_none());
+        assert!(reactor.socket(second).is_some());
+
+        // 旧 Token 的操作不能影响复用槽位上的新 socket
+        reactor.shutdown(first);
+        reactor.close(first);
+        assert!(reactor.socket(second).is_some());
+    }
+
+    #[test]
+    fn test_functor_and_timer() {
+        let mut event_loop_thread = EventLoopThread::<UdpSocket>::new(2).unwrap();
+        let remote = event_loop_thread.get_remote();
+        event_loop_thread.run();
+
+        let (tx, rx) = mpsc::channel();
+        let functor_tx = tx.clone();
+        remote.run_in_loop(move |reactor| {
+            let in_loop = reactor.get_remote().is_in_loop_thread();
+            functor_tx.send(("functor", in_loop)).unwrap();
+        });
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Ok(("functor", true))
+        );
+        assert!(!remote.is_in_loop_thread());
+
+        // 持有 reactor 时立即执行，通过 remote 投递的等当前 functor 返回后才执行
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (done_tx, done_rx) = mpsc::channel();
+        let outer = order.clone();
+        remote.run_in_loop(move |reactor| {
+            let queued = outer.clone();
+            reactor.get_remote().run_in_loop(move |_| {
+                queued.lock().unwrap().push("queued");
+                done_tx.send(()).unwrap();
+            });
+            let inner = outer.clone();
+            reactor.run_in_loop(move |_| inner.lock().unwrap().push("inner"));
+            outer.lock().unwrap().push("outer");
+        });
+        done_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(*order.lock().unwrap(), ["inner", "outer", "queued"]);
+
+        let timer_tx = tx.clone();
+        remote.run_after(Duration::from_millis(20), move || {
+            timer_tx.send(("timer", true)).unwrap();
+        });
+        let cancelled = remote.run_after(Duration::from_millis(10), move || {
+            tx.send(("cancelled", true)).unwrap();
+        });
+        remote.cancel(cancelled);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(("timer", true)));
+
+        event_loop_thread.quit();
+        event_loop_thread.wait();
+    }
+
+    #[test]
+    fn test_loop_identity() {
+        let mut first = EventLoopThread::<UdpSocket>::new(2).unwrap();
+        let mut second = EventLoopThread::<UdpSocket>::new(2).unwrap();
+        let first_remote = first.get_remote();
+        let second_remote = second.get_remote();
+        first.run();
+        second.run();
+        assert!(LoopId::current().is_none());
+        assert!(Reactor::<UdpSocket>::current().is_none());
+
+        let (tx, rx) = mpsc::channel();
+        let other = second_remote.clone();
+        first_remote.run_in_loop(move |reactor| {
+            let current = Reactor::<UdpSocket>::current().unwrap();
+            current.assert_in_loop_thread();
+            tx.send((
+                LoopId::current() == Some(reactor.loop_id()),
+                current.is_in_loop_thread(),
+                // 其他 reactor 的 remote 和承载其他 socket 类型的查询都不属于当前 loop
+                other.is_in_loop_thread(),
+                Reactor::<TcpConnection>::current().is_some(),
+            ))
+            .unwrap();
+        });
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Ok((true, true, false, false))
+        );
+
+        first.quit();
+        second.quit();
+        first.wait();
+        second.wait();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "loop thread")]
+    fn test_assert_in_loop_thread() {
+        let reactor = Reactor::<UdpSocket>::new(2).unwrap();
+        reactor.get_remote().assert_in_loop_thread();
+    }
+}
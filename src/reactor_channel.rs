@@ -1,44 +1,231 @@
-use std::sync::{
-    Arc, Mutex,
-    atomic::{AtomicU64, Ordering},
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
 };
 
 use log::error;
 use mio::Waker;
 
-use crate::reactor::u64_current_thread_id;
+use crate::{mpsc_queue::MpscQueue, reactor::LoopId};
+
+// 数据信号超过容量时的处理方式，控制信号和 loop 线程自己发送的信号不受限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // 阻塞发送线程直到 reactor 取走信号
+    Block,
+    // 拒绝新的信号，由发送方处理错误
+    Reject,
+    // 丢弃最旧的可丢弃信号（UDP 的 Send）。TCP 写入丢弃后字节流会损坏，
+    // 所以不会被丢弃；没有可丢弃的信号或新信号是 TCP 写入时按 Reject 处理
+    DropOldest,
+}
+
+impl OverflowPolicy {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => OverflowPolicy::Block,
+            1 => OverflowPolicy::Reject,
+            _ => OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SignalStats {
+    // 队列中的数据信号数
+    pub len: usize,
+    pub rejected: u64,
+    pub dropped: u64,
+    // 发送线程因队列满而阻塞的次数
+    pub blocked: u64,
+}
+
+// 只有数据信号计入容量，只有可丢弃的数据信号会被 DropOldest 丢弃
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Control,
+    Data,
+    Droppable,
+}
+
+impl Kind {
+    fn is_data(self) -> bool {
+        self != Kind::Control
+    }
+}
+
+struct Channel<T> {
+    queue: MpscQueue<(T, Kind)>,
+    // pop 必须互斥：消费者取信号，或 DropOldest 时生产者丢弃最旧的信号。
+    // 丢弃时取出的其它信号暂存在这里，保持原来的顺序
+    front: Mutex<VecDeque<(T, Kind)>>,
+    space: Condvar,
+    len: AtomicUsize,
+    capacity: AtomicUsize,
+    policy: AtomicU8,
+    rejected: AtomicU64,
+    dropped: AtomicU64,
+    blocked: AtomicU64,
+    // 已经唤醒过 reactor 且它还没有取走信号，此时不必再次唤醒
+    pending: AtomicBool,
+}
+
+impl<T> Channel<T> {
+    // 队列未满时占用一个位置
+    fn try_reserve(&self) -> bool {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        self.len
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |len| {
+                (len < capacity).then_some(len + 1)
+            })
+            .is_ok()
+    }
+
+    // 弹出最旧的可丢弃信号，途中遇到的其它信号移到 front。
+    // 队列中没有可丢弃的信号（或其它生产者占了位置还没有 push）时返回 false
+    fn drop_oldest(&self) -> bool {
+        let mut front = self.front.lock().unwrap();
+        while let Some((item, kind)) = unsafe { self.queue.pop() } {
+            if kind == Kind::Droppable {
+                self.len.fetch_sub(1, Ordering::AcqRel);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                drop(front);
+                drop(item);
+                return true;
+            }
+            front.push_back((item, kind));
+        }
+        false
+    }
+
+    // 丢弃最旧的信号直到占到位置，丢弃后空出的位置可能被其它生产者抢走
+    fn drop_until_reserved(&self) -> bool {
+        while self.drop_oldest() {
+            if self.try_reserve() {
+                return true;
+            }
+        }
+        false
+    }
+}
 
 pub struct Sender<T>
 where
     T: Send,
 {
-    queue: Arc<Mutex<Vec<T>>>,
+    channel: Arc<Channel<T>>,
     waker: Arc<Waker>,
-    thread_id: Arc<AtomicU64>,
+    loop_id: LoopId,
 }
 
 impl<T> Sender<T>
 where
     T: Send,
 {
-    pub fn new(queue: Arc<Mutex<Vec<T>>>, waker: Arc<Waker>, thread_id: Arc<AtomicU64>) -> Self {
-        Self {
-            queue,
-            waker,
-            thread_id,
+    // 控制信号，不受容量限制
+    pub fn send(&self, item: T) {
+        self.push(item, Kind::Control);
+        if !self.is_in_loop_thread() {
+            self.wake();
         }
     }
 
-    pub fn send(&self, item: T) {
-        {
-            let mut queue = self.queue.lock().unwrap();
-            queue.push(item);
+    // 无论在哪个线程都唤醒 reactor
+    pub fn send_and_wake(&self, item: T) {
+        self.push(item, Kind::Control);
+        self.wake();
+    }
+
+    // 数据信号，队列满时按 OverflowPolicy 处理，被拒绝时返回原信号。
+    // 不会被 DropOldest 丢弃，例如 TCP 写入
+    pub fn try_send(&self, item: T) -> Result<(), T> {
+        self.try_send_kind(item, Kind::Data)
+    }
+
+    // 队列满且策略为 DropOldest 时可以被丢弃的数据信号，例如 UDP 数据报
+    pub fn try_send_droppable(&self, item: T) -> Result<(), T> {
+        self.try_send_kind(item, Kind::Droppable)
+    }
+
+    fn try_send_kind(&self, item: T, kind: Kind) -> Result<(), T> {
+        if self.is_in_loop_thread() {
+            // loop 线程阻塞会造成死锁，自己发送的信号也会在本轮被取走
+            self.channel.len.fetch_add(1, Ordering::AcqRel);
+            self.push(item, kind);
+            return Ok(());
         }
-        if self.thread_id.load(Ordering::Relaxed) != u64_current_thread_id() {
-            if self.waker.wake().is_err() {
-                error!("Failed to wake reactor up!")
+        if !self.channel.try_reserve() {
+            match self.policy() {
+                OverflowPolicy::Block => self.wait_for_space(),
+                OverflowPolicy::DropOldest
+                    if kind == Kind::Droppable && self.channel.drop_until_reserved() => {}
+                OverflowPolicy::Reject | OverflowPolicy::DropOldest => {
+                    self.channel.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(item);
+                }
             }
         }
+        self.push(item, kind);
+        self.wake();
+        Ok(())
+    }
+
+    // capacity 为 usize::MAX 时不限制
+    pub fn set_limit(&self, capacity: usize, policy: OverflowPolicy) {
+        self.channel.capacity.store(capacity, Ordering::Relaxed);
+        self.channel.policy.store(policy as u8, Ordering::Relaxed);
+        self.channel.space.notify_all();
+    }
+
+    pub fn stats(&self) -> SignalStats {
+        SignalStats {
+            len: self.channel.len.load(Ordering::Relaxed),
+            rejected: self.channel.rejected.load(Ordering::Relaxed),
+            dropped: self.channel.dropped.load(Ordering::Relaxed),
+            blocked: self.channel.blocked.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn is_in_loop_thread(&self) -> bool {
+        self.loop_id.is_current()
+    }
+
+    fn policy(&self) -> OverflowPolicy {
+        OverflowPolicy::from_u8(self.channel.policy.load(Ordering::Relaxed))
+    }
+
+    fn wait_for_space(&self) {
+        self.channel.blocked.fetch_add(1, Ordering::Relaxed);
+        // 确保 reactor 醒来取走信号
+        self.wake();
+        let mut front = self.channel.front.lock().unwrap();
+        while !self.channel.try_reserve() {
+            // 超时后重试，避免与 take_all 的通知错过
+            front = self
+                .channel
+                .space
+                .wait_timeout(front, Duration::from_millis(10))
+                .unwrap()
+                .0;
+        }
+    }
+
+    fn push(&self, item: T, kind: Kind) {
+        self.channel.queue.push((item, kind));
+    }
+
+    // 合并唤醒：reactor 取走信号之前只唤醒一次
+    fn wake(&self) {
+        if self.channel.pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if self.waker.wake().is_err() {
+            error!("Failed to wake reactor up!")
+        }
     }
 }
 
@@ -48,41 +235,209 @@ where
 {
     fn clone(&self) -> Self {
         Sender {
-            queue: self.queue.clone(),
+            channel: self.channel.clone(),
             waker: Arc::clone(&self.waker),
-            thread_id: Arc::clone(&self.thread_id),
+            loop_id: self.loop_id,
         }
     }
 }
 
+// 只能有一个消费者，所以不实现 Clone
 pub struct Receiver<T>
 where
     T: Send,
 {
-    pub queue: Arc<Mutex<Vec<T>>>,
+    channel: Arc<Channel<T>>,
 }
 
 impl<T> Receiver<T>
 where
     T: Send,
 {
-    pub fn new(queue: Arc<Mutex<Vec<T>>>) -> Self {
-        Self { queue }
+    pub fn new() -> Self {
+        Self {
+            channel: Arc::new(Channel {
+                queue: MpscQueue::new(),
+                front: Mutex::new(VecDeque::new()),
+                space: Condvar::new(),
+                len: AtomicUsize::new(0),
+                capacity: AtomicUsize::new(usize::MAX),
+                policy: AtomicU8::new(OverflowPolicy::Block as u8),
+                rejected: AtomicU64::new(0),
+                dropped: AtomicU64::new(0),
+                blocked: AtomicU64::new(0),
+                pending: AtomicBool::new(false),
+            }),
+        }
     }
 
-    pub fn take_all(&self) -> Vec<T> {
-        let mut queue = self.queue.lock().unwrap();
-        std::mem::take(&mut *queue)
+    pub fn sender(&self, waker: Arc<Waker>, loop_id: LoopId) -> Sender<T> {
+        Sender {
+            channel: self.channel.clone(),
+            waker,
+            loop_id,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let front = self.channel.front.lock().unwrap();
+        front.is_empty() && unsafe { self.channel.queue.is_empty() }
+    }
+
+    pub fn take_all(&mut self) -> Vec<T> {
+        // 先清除标记再取，之后到达的信号会重新唤醒 reactor
+        self.channel.pending.store(false, Ordering::SeqCst);
+        let mut front = self.channel.front.lock().unwrap();
+        let mut items = Vec::new();
+        let mut data_count = 0;
+        let queued = std::iter::from_fn(|| unsafe { self.channel.queue.pop() });
+        for (item, kind) in front.drain(..).chain(queued) {
+            data_count += kind.is_data() as usize;
+            items.push(item);
+        }
+        drop(front);
+        if data_count > 0 {
+            self.channel.len.fetch_sub(data_count, Ordering::AcqRel);
+            self.channel.space.notify_all();
+        }
+        items
     }
 }
 
-impl<T> Clone for Receiver<T>
+impl<T> Default for Receiver<T>
 where
     T: Send,
 {
-    fn clone(&self) -> Self {
-        Receiver {
-            queue: self.queue.clone(),
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, atomic::Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use mio::{Events, Poll, Token, Waker};
+
+    use super::{OverflowPolicy, Receiver, Sender};
+    use crate::reactor::LoopId;
+
+    fn channel() -> (Poll, Receiver<u32>, Sender<u32>) {
+        let poll = Poll::new().unwrap();
+        let waker = Arc::new(Waker::new(poll.registry(), Token(0)).unwrap());
+        let receiver = Receiver::new();
+        // 没有运行的 loop，发送方都不在 loop 线程中
+        let sender = receiver.sender(waker, LoopId::next());
+        (poll, receiver, sender)
+    }
+
+    #[test]
+    fn test_wake_coalescing() {
+        let (mut poll, mut receiver, sender) = channel();
+
+        let producers: Vec<_> = (0..4)
+            .map(|_| {
+                let sender = sender.clone();
+                thread::spawn(move || (0..1000).for_each(|i| sender.send(i)))
+            })
+            .collect();
+        producers.into_iter().for_each(|p| p.join().unwrap());
+        assert!(receiver.channel.pending.load(Ordering::SeqCst));
+
+        let mut events = Events::with_capacity(8);
+        poll.poll(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(events.iter().count(), 1);
+        assert_eq!(receiver.take_all().len(), 4000);
+        assert!(receiver.is_empty());
+        assert!(!receiver.channel.pending.load(Ordering::SeqCst));
+
+        // 取走之后的信号会再次唤醒
+        sender.send(0);
+        poll.poll(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(events.iter().count(), 1);
+        assert_eq!(receiver.take_all(), vec![0]);
+    }
+
+    #[test]
+    fn test_reject() {
+        let (_poll, mut receiver, sender) = channel();
+        sender.set_limit(2, OverflowPolicy::Reject);
+        assert_eq!(sender.try_send(1), Ok(()));
+        assert_eq!(sender.try_send(2), Ok(()));
+        assert_eq!(sender.try_send(3), Err(3));
+        // 控制信号不受限制
+        sender.send(100);
+        assert_eq!(sender.stats().len, 2);
+        assert_eq!(sender.stats().rejected, 1);
+
+        assert_eq!(receiver.take_all(), vec![1, 2, 100]);
+        assert_eq!(sender.stats().len, 0);
+        assert_eq!(sender.try_send(4), Ok(()));
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (_poll, mut receiver, sender) = channel();
+        sender.set_limit(2, OverflowPolicy::DropOldest);
+        sender.send(100);
+        for i in 1..=4 {
+            assert_eq!(sender.try_send_droppable(i), Ok(()));
         }
+        assert_eq!(sender.stats().dropped, 2);
+        assert!(!receiver.is_empty());
+        // 控制信号不会被丢弃，顺序保持不变
+        assert_eq!(receiver.take_all(), vec![100, 3, 4]);
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn test_drop_oldest_falls_back_to_reject() {
+        let (_poll, mut receiver, sender) = channel();
+        sender.set_limit(2, OverflowPolicy::DropOldest);
+        assert_eq!(sender.try_send(1), Ok(()));
+        assert_eq!(sender.try_send_droppable(2), Ok(()));
+        // 不可丢弃的信号在队列满时被拒绝，而不是丢弃别的信号
+        assert_eq!(sender.try_send(3), Err(3));
+        assert_eq!(sender.try_send_droppable(4), Ok(()));
+        assert_eq!(sender.stats().dropped, 1);
+
+        assert_eq!(sender.try_send_droppable(5), Ok(()));
+        sender.send(100);
+        assert_eq!(sender.try_send_droppable(6), Ok(()));
+        assert_eq!(receiver.take_all(), vec![1, 100, 6]);
+        // 没有可丢弃的信号时按 Reject 处理，不会超出容量
+        assert_eq!(sender.try_send(7), Ok(()));
+        assert_eq!(sender.try_send(8), Ok(()));
+        assert_eq!(sender.try_send_droppable(9), Err(9));
+        assert_eq!(sender.stats().len, 2);
+        assert_eq!(sender.stats().rejected, 2);
+        assert_eq!(receiver.take_all(), vec![7, 8]);
+    }
+
+    #[test]
+    fn test_block() {
+        let (_poll, mut receiver, sender) = channel();
+        sender.set_limit(1, OverflowPolicy::Block);
+        assert_eq!(sender.try_send(1), Ok(()));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let blocked_sender = sender.clone();
+        let producer = thread::spawn(move || {
+            blocked_sender.try_send(2).unwrap();
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        assert_eq!(sender.stats().blocked, 1);
+
+        assert_eq!(receiver.take_all(), vec![1]);
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        producer.join().unwrap();
+        assert_eq!(receiver.take_all(), vec![2]);
     }
 }
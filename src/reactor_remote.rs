@@ -1,6 +1,13 @@
+use std::time::{Duration, Instant};
+
 use log::trace;
 
-use crate::{ReactorSocket, reactor::ReactorSignal, reactor_channel::Sender};
+use crate::{
+    Reactor, ReactorSocket,
+    reactor::ReactorSignal,
+    reactor_channel::{OverflowPolicy, Sender, SignalStats},
+    timer_queue::{TimerCallback, TimerId},
+};
 
 pub struct ReactorRemote<S>
 where
@@ -36,8 +43,91 @@ where
         self.sender.send(ReactorSignal::Register(socket));
     }
 
+    // 在 loop 线程中执行 functor。已持有 &mut Reactor 时用 Reactor::run_in_loop 立即执行；
+    // 在 loop 线程的回调中 reactor 正在分发事件，functor 在本轮分发结束后执行，不唤醒 poll
+    pub fn run_in_loop<F>(&self, functor: F)
+    where
+        F: FnOnce(&mut Reactor<S>) + Send + 'static,
+    {
+        self.sender.send(ReactorSignal::Functor(Box::new(functor)));
+    }
+
+    // 总是放入队列并唤醒 reactor，functor 在下一轮循环中执行
+    pub fn queue_in_loop<F>(&self, functor: F)
+    where
+        F: FnOnce(&mut Reactor<S>) + Send + 'static,
+    {
+        self.sender
+            .send_and_wake(ReactorSignal::Functor(Box::new(functor)));
+    }
+
+    pub fn is_in_loop_thread(&self) -> bool {
+        self.sender.is_in_loop_thread()
+    }
+
+    // 只在 debug 构建中检查，不在 loop 线程时 panic
+    #[track_caller]
+    pub fn assert_in_loop_thread(&self) {
+        debug_assert!(
+            self.is_in_loop_thread(),
+            "must be called in the reactor's loop thread"
+        );
+    }
+
+    pub fn run_at<F>(&self, when: Instant, callback: F) -> TimerId
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let id = TimerId::next();
+        self.sender.send(ReactorSignal::AddTimer(
+            id,
+            when,
+            TimerCallback::Once(Box::new(callback)),
+        ));
+        id
+    }
+
+    pub fn run_after<F>(&self, delay: Duration, callback: F) -> TimerId
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.run_at(Instant::now() + delay, callback)
+    }
+
+    pub fn run_every<F>(&self, interval: Duration, callback: F) -> TimerId
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let id = TimerId::next();
+        self.sender.send(ReactorSignal::AddTimer(
+            id,
+            Instant::now() + interval,
+            TimerCallback::Repeat(Box::new(callback), interval),
+        ));
+        id
+    }
+
+    pub fn cancel(&self, timer_id: TimerId) {
+        self.sender.send(ReactorSignal::CancelTimer(timer_id));
+    }
+
     pub fn quit(&self) {
         trace!("Sending quit signal to reactor");
         self.sender.send(ReactorSignal::Quit);
     }
+
+    // 限制信号队列中写入等数据信号的数量，capacity 为 usize::MAX 时不限制
+    pub fn set_signal_limit(&self, capacity: usize, policy: OverflowPolicy) {
+        self.sender.set_limit(capacity, policy);
+    }
+
+    pub fn signal_stats(&self) -> SignalStats {
+        self.sender.stats()
+    }
+
+    // 优雅关闭 reactor 上的所有 socket，全部关闭或超时后退出
+    pub fn drain(&self, timeout: Duration) {
+        trace!("Sending drain signal to reactor");
+        self.run_in_loop(move |reactor| reactor.drain(timeout));
+    }
 }
@@ -11,4 +11,50 @@ pub trait ReactorSocket: Send {
     fn poll_token(&self) -> Option<mio::Token>;
     fn set_poll_token(&mut self, token: mio::Token);
     fn send(&mut self, addr: std::net::SocketAddr, data: &[u8]) -> std::io::Result<usize>;
+
+    // 一次写入多个数据块，默认只写第一个非空的块
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        match bufs.iter().find(|buf| !buf.is_empty()) {
+            Some(buf) => self.write(buf),
+            None => Ok(0),
+        }
+    }
+
+    // 暂存未写完的数据块，默认复制到 stash_output
+    fn stash_chunk(&mut self, chunk: bytes::Bytes) {
+        self.stash_output(&chunk);
+    }
+
+    // 输出缓冲区被清空时调用
+    fn handle_write_complete(&mut self) {}
+
+    // 收到 ShutDown 信号时调用，返回 true 表示由 reactor 立即关闭 socket
+    fn handle_shutdown(&mut self) -> bool {
+        true
+    }
+
+    // 正在优雅关闭的 socket 不再接受新的写入
+    fn is_disconnecting(&self) -> bool {
+        false
+    }
+}
+
+// reactor 中的 socket 类型可以容纳 S，用于在一个 reactor 中混合多种 socket。
+// 每种 socket 都可以容纳自身
+pub trait AsSocket<S>: ReactorSocket + From<S> {
+    fn as_socket(&self) -> Option<&S>;
+    fn as_socket_mut(&mut self) -> Option<&mut S>;
+}
+
+impl<S> AsSocket<S> for S
+where
+    S: ReactorSocket,
+{
+    fn as_socket(&self) -> Option<&S> {
+        Some(self)
+    }
+
+    fn as_socket_mut(&mut self) -> Option<&mut S> {
+        Some(self)
+    }
 }
@@ -1,155 +1,447 @@
-use core::panic;
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
-use log::warn;
+use mio::net::TcpListener;
 
 use crate::{
     Acceptor, EventLoopThread, EventLoopThreadPool, Reactor, ReactorRemote, ReactorSocket,
-    TcpConnection, UdpSocket,
-    callbacks::{ConnectionCallback, DatagramCallback, MessageCallback},
+    TcpConnection, TcpOptions, UdpSocket,
+    acceptor::DEFAULT_LISTEN_BACKLOG,
+    callbacks::{
+        ConnectionCallback, DatagramCallback, HighWaterMarkCallback, IdleCallback, MessageCallback,
+        WriteCompleteCallback, default_connection_callback, default_message_callback,
+    },
+    error::{Error, Result, parse_addr},
+    event_loop_thread_pool::DEFAULT_SOCK_CAPACITY,
+    reactor::DEFAULT_EVENTS_CAPACITY,
+    reactor_channel::OverflowPolicy,
 };
 
+pub struct ServerBuilder {
+    tcp_addrs: Vec<String>,
+    udp_addrs: Vec<String>,
+    io_threads: usize,
+    sock_capacity: usize,
+    events_capacity: usize,
+    listen_backlog: u32,
+    connection_callback: Option<ConnectionCallback>,
+    message_callback: Option<MessageCallback>,
+    datagram_callback: Option<DatagramCallback>,
+    write_complete_callback: Option<WriteCompleteCallback>,
+    high_water_mark_callback: Option<(HighWaterMarkCallback, usize)>,
+    max_input_buffer: Option<usize>,
+    idle_timeout: Option<Duration>,
+    idle_callback: Option<IdleCallback>,
+    tcp_options: Option<TcpOptions>,
+    signal_limit: Option<(usize, OverflowPolicy)>,
+}
+
+impl ServerBuilder {
+    pub fn new() -> Self {
+        ServerBuilder {
+            tcp_addrs: Vec::new(),
+            udp_addrs: Vec::new(),
+            io_threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            sock_capacity: DEFAULT_SOCK_CAPACITY,
+            events_capacity: DEFAULT_EVENTS_CAPACITY,
+            listen_backlog: DEFAULT_LISTEN_BACKLOG,
+            connection_callback: None,
+            message_callback: None,
+            datagram_callback: None,
+            write_complete_callback: None,
+            high_water_mark_callback: None,
+            max_input_buffer: None,
+            idle_timeout: None,
+            idle_callback: None,
+            tcp_options: None,
+            signal_limit: None,
+        }
+    }
+
+    // 可以多次调用，每个地址绑定一个 Acceptor，共用 acceptor 线程和 io 线程
+    pub fn tcp(mut self, addr: impl Into<String>) -> Self {
+        self.tcp_addrs.push(addr.into());
+        self
+    }
+
+    // 可以多次调用，所有 UDP socket 共用 UDP 线程和 datagram_callback
+    pub fn udp(mut self, addr: impl Into<String>) -> Self {
+        self.udp_addrs.push(addr.into());
+        self
+    }
+
+    // 处理 TCP 连接的 io 线程数，不包括 acceptor 和 UDP 线程
+    pub fn io_threads(mut self, io_threads: usize) -> Self {
+        self.io_threads = io_threads;
+        self
+    }
+
+    // 每个 io reactor 预分配的 socket 槽位数
+    pub fn sock_capacity(mut self, sock_capacity: usize) -> Self {
+        self.sock_capacity = sock_capacity;
+        self
+    }
+
+    // 每个 io reactor 单次 poll 最多返回的事件数
+    pub fn events_capacity(mut self, events_capacity: usize) -> Self {
+        self.events_capacity = events_capacity;
+        self
+    }
+
+    pub fn listen_backlog(mut self, listen_backlog: u32) -> Self {
+        self.listen_backlog = listen_backlog;
+        self
+    }
+
+    pub fn connection_callback(mut self, callback: ConnectionCallback) -> Self {
+        self.connection_callback = Some(callback);
+        self
+    }
+
+    pub fn message_callback(mut self, callback: MessageCallback) -> Self {
+        self.message_callback = Some(callback);
+        self
+    }
+
+    pub fn datagram_callback(mut self, callback: DatagramCallback) -> Self {
+        self.datagram_callback = Some(callback);
+        self
+    }
+
+    pub fn write_complete_callback(mut self, callback: WriteCompleteCallback) -> Self {
+        self.write_complete_callback = Some(callback);
+        self
+    }
+
+    pub fn high_water_mark_callback(
+        mut self,
+        callback: HighWaterMarkCallback,
+        high_water_mark: usize,
+    ) -> Self {
+        self.high_water_mark_callback = Some((callback, high_water_mark));
+        self
+    }
+
+    pub fn max_input_buffer(mut self, max_input_buffer: usize) -> Self {
+        self.max_input_buffer = Some(max_input_buffer);
+        self
+    }
+
+    // 连接超过 idle_timeout 没有读写时被关闭
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    // 设置后连接空闲时不再关闭，而是调用回调，可以用来发送心跳
+    pub fn idle_callback(mut self, callback: IdleCallback) -> Self {
+        self.idle_callback = Some(callback);
+        self
+    }
+
+    // 应用到每个接受的 TCP 连接，例如 nodelay
+    pub fn tcp_options(mut self, options: TcpOptions) -> Self {
+        self.tcp_options = Some(options);
+        self
+    }
+
+    // 限制每个 io reactor 和 UDP reactor 信号队列中的写入数，防止慢 reactor 占满内存
+    pub fn signal_limit(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        self.signal_limit = Some((capacity, policy));
+        self
+    }
+
+    // 供分帧层包装用户设置的连接回调
+    pub(crate) fn take_connection_callback(&mut self) -> Option<ConnectionCallback> {
+        self.connection_callback.take()
+    }
+
+    pub(crate) fn has_message_callback(&self) -> bool {
+        self.message_callback.is_some()
+    }
+
+    pub fn build(self) -> Result<Server> {
+        if self.tcp_addrs.is_empty() && self.udp_addrs.is_empty() {
+            return Err(invalid_config("must listen on tcp or udp or both"));
+        }
+        let tcp_addrs = self
+            .tcp_addrs
+            .iter()
+            .map(|addr| parse_addr(addr))
+            .collect::<Result<Vec<_>>>()?;
+        let udp_addrs = self
+            .udp_addrs
+            .iter()
+            .map(|addr| parse_addr(addr))
+            .collect::<Result<Vec<_>>>()?;
+        let has_tcp = !tcp_addrs.is_empty();
+        let has_udp = !udp_addrs.is_empty();
+        if has_udp && self.datagram_callback.is_none() {
+            return Err(invalid_config("udp server requires a datagram callback"));
+        }
+        if !has_tcp {
+            if self.connection_callback.is_some()
+                || self.message_callback.is_some()
+                || self.write_complete_callback.is_some()
+                || self.high_water_mark_callback.is_some()
+                || self.idle_callback.is_some()
+            {
+                return Err(invalid_config(
+                    "tcp callbacks are set without a tcp address",
+                ));
+            }
+            if self.tcp_options.is_some() {
+                return Err(invalid_config("tcp_options is set without a tcp address"));
+            }
+            if self.idle_timeout.is_some() {
+                return Err(invalid_config("idle_timeout is set without a tcp address"));
+            }
+            if self.max_input_buffer.is_some() {
+                return Err(invalid_config(
+                    "max_input_buffer is set without a tcp address",
+                ));
+            }
+        }
+        if self.io_threads == 0 {
+            return Err(invalid_config("io_threads must be at least 1"));
+        }
+        if self.sock_capacity == 0 {
+            return Err(invalid_config("sock_capacity must be at least 1"));
+        }
+        if self.events_capacity == 0 {
+            return Err(invalid_config("events_capacity must be at least 1"));
+        }
+        if self.listen_backlog == 0 || self.listen_backlog > i32::MAX as u32 {
+            return Err(invalid_config("listen_backlog must be in 1..=i32::MAX"));
+        }
+        if let Some((_, 0)) = self.high_water_mark_callback {
+            return Err(invalid_config("high_water_mark must be at least 1"));
+        }
+        if self.max_input_buffer == Some(0) {
+            return Err(invalid_config("max_input_buffer must be at least 1"));
+        }
+        if self.idle_timeout == Some(Duration::ZERO) {
+            return Err(invalid_config("idle_timeout must be greater than zero"));
+        }
+        if self.idle_callback.is_some() && self.idle_timeout.is_none() {
+            return Err(invalid_config("idle_callback requires an idle_timeout"));
+        }
+        if let Some((0, _)) = self.signal_limit {
+            return Err(invalid_config("signal queue capacity must be at least 1"));
+        }
+
+        // 在 build 时绑定，端口为 0 时可以在 run 之前拿到实际端口
+        let listeners = tcp_addrs
+            .into_iter()
+            .map(|addr| Acceptor::bind(addr, self.listen_backlog))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let udp_sockets = udp_addrs
+            .into_iter()
+            .map(mio::net::UdpSocket::bind)
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let local_addrs = LocalAddrs {
+            tcp: listeners
+                .iter()
+                .map(|l| l.local_addr())
+                .collect::<std::io::Result<_>>()?,
+            udp: udp_sockets
+                .iter()
+                .map(|s| s.local_addr())
+                .collect::<std::io::Result<_>>()?,
+        };
+
+        let udp_reactor = has_udp
+            .then(|| Reactor::new(udp_sockets.len() + 1))
+            .transpose()?;
+        let event_loop_thread_pool = has_tcp
+            .then(|| {
+                EventLoopThreadPool::with_capacity(
+                    self.io_threads,
+                    self.sock_capacity,
+                    self.events_capacity,
+                )
+            })
+            .transpose()?;
+        if let Some((capacity, policy)) = self.signal_limit {
+            let udp_remote = udp_reactor.as_ref().map(|r| r.get_remote());
+            let tcp_remotes = event_loop_thread_pool.iter().flat_map(|p| p.get_remotes());
+            for remote in tcp_remotes {
+                remote.set_signal_limit(capacity, policy);
+            }
+            if let Some(remote) = udp_remote {
+                remote.set_signal_limit(capacity, policy);
+            }
+        }
+
+        Ok(Server {
+            local_addrs,
+            acceptor_reactor: has_tcp
+                .then(|| Reactor::new(listeners.len() + 1))
+                .transpose()?,
+            listeners,
+            udp_sockets,
+            udp_reactor,
+            event_loop_thread_pool,
+            connection_callback: self
+                .connection_callback
+                .unwrap_or_else(|| std::sync::Arc::new(default_connection_callback)),
+            message_callback: self
+                .message_callback
+                .unwrap_or_else(|| std::sync::Arc::new(default_message_callback)),
+            datagram_callback: self.datagram_callback,
+            write_complete_callback: self.write_complete_callback,
+            high_water_mark_callback: self.high_water_mark_callback,
+            max_input_buffer: self.max_input_buffer,
+            idle_timeout: self.idle_timeout,
+            idle_callback: self.idle_callback,
+            tcp_options: self.tcp_options,
+        })
+    }
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn invalid_config(msg: &str) -> Error {
+    Error::InvalidConfig(msg.to_string())
+}
+
+// 按 ServerBuilder::tcp 和 udp 的调用顺序排列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalAddrs {
+    pub tcp: Vec<SocketAddr>,
+    pub udp: Vec<SocketAddr>,
+}
+
 pub struct Server {
-    addr: String,
+    local_addrs: LocalAddrs,
+    listeners: Vec<TcpListener>,
+    udp_sockets: Vec<mio::net::UdpSocket>,
     acceptor_reactor: Option<Reactor<Acceptor>>,
     udp_reactor: Option<Reactor<UdpSocket>>,
     event_loop_thread_pool: Option<EventLoopThreadPool<TcpConnection>>,
-    connection_callback: Option<ConnectionCallback>,
-    message_callback: Option<MessageCallback>,
+    connection_callback: ConnectionCallback,
+    message_callback: MessageCallback,
     datagram_callback: Option<DatagramCallback>,
-    tcp: bool,
-    udp: bool,
+    write_complete_callback: Option<WriteCompleteCallback>,
+    high_water_mark_callback: Option<(HighWaterMarkCallback, usize)>,
+    max_input_buffer: Option<usize>,
+    idle_timeout: Option<Duration>,
+    idle_callback: Option<IdleCallback>,
+    tcp_options: Option<TcpOptions>,
 }
 
 impl Server {
-    pub fn tcp_server(
-        addr: String,
-        mut num_threads: usize,
-        message_callback: MessageCallback,
-        connection_callback: ConnectionCallback,
-    ) -> Self {
-        if num_threads == 0 {
-            warn!("Number of threads is 0, using 1 instead");
-            num_threads = 1;
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+
+    // 实际绑定的地址，监听端口 0 时为内核分配的端口
+    pub fn local_addrs(&self) -> LocalAddrs {
+        self.local_addrs.clone()
+    }
+
+    fn new_acceptor(&self, listener: TcpListener) -> Acceptor {
+        let io_reactors = self.event_loop_thread_pool.as_ref().unwrap().get_remotes();
+        let mut acceptor = Acceptor::with_listener(
+            listener,
+            io_reactors,
+            self.connection_callback.clone(),
+            self.message_callback.clone(),
+        );
+        if let Some(callback) = &self.write_complete_callback {
+            acceptor.set_write_complete_callback(callback.clone());
         }
-        let reactor = Reactor::new(2);
-        Server {
-            addr,
-            acceptor_reactor: Some(reactor),
-            udp_reactor: None,
-            event_loop_thread_pool: Some(EventLoopThreadPool::new(num_threads)),
-            connection_callback: Some(connection_callback),
-            message_callback: Some(message_callback),
-            datagram_callback: None,
-            tcp: true,
-            udp: false,
+        if let Some((callback, high_water_mark)) = &self.high_water_mark_callback {
+            acceptor.set_high_water_mark_callback(callback.clone(), *high_water_mark);
+        }
+        if let Some(max_input_buffer) = self.max_input_buffer {
+            acceptor.set_max_input_buffer(max_input_buffer);
         }
+        if let Some(idle_timeout) = self.idle_timeout {
+            acceptor.set_idle_timeout(idle_timeout);
+        }
+        if let Some(callback) = &self.idle_callback {
+            acceptor.set_idle_callback(callback.clone());
+        }
+        if let Some(options) = &self.tcp_options {
+            acceptor.set_tcp_options(options.clone());
+        }
+        acceptor
     }
 
-    pub fn udp_server(addr: String, datagram_callback: DatagramCallback) -> Self {
-        let reactor = Reactor::new(2);
-        Server {
-            addr,
-            acceptor_reactor: None,
-            udp_reactor: Some(reactor),
-            event_loop_thread_pool: None,
-            connection_callback: None,
-            message_callback: None,
-            datagram_callback: Some(datagram_callback),
-            tcp: false,
-            udp: true,
+    // 所有 Acceptor 注册到同一个 acceptor reactor
+    fn get_acceptor_reactor(&mut self) -> Result<Option<Reactor<Acceptor>>> {
+        let Some(mut reactor) = self.acceptor_reactor.take() else {
+            return Ok(None);
+        };
+        for (listener, addr) in std::mem::take(&mut self.listeners)
+            .into_iter()
+            .zip(&self.local_addrs.tcp)
+        {
+            reactor.register(self.new_acceptor(listener))?;
+            println!("TCP Server is running on {}", addr);
         }
+        Ok(Some(reactor))
     }
 
-    pub fn tcp_udp_server(
-        addr: String,
-        mut num_threads: usize,
-        message_callback: MessageCallback,
-        connection_callback: ConnectionCallback,
-        datagram_callback: DatagramCallback,
-    ) -> Self {
-        if num_threads == 0 {
-            warn!("Number of threads is 0, using 1 instead");
-            num_threads = 1;
+    fn get_udp_reactor(&mut self) -> Result<Option<Reactor<UdpSocket>>> {
+        let Some(mut reactor) = self.udp_reactor.take() else {
+            return Ok(None);
+        };
+        let datagram_callback = self.datagram_callback.take().unwrap();
+        for (socket, addr) in std::mem::take(&mut self.udp_sockets)
+            .into_iter()
+            .zip(&self.local_addrs.udp)
+        {
+            let udp_socket =
+                UdpSocket::new(socket, datagram_callback.clone(), reactor.get_sender());
+            reactor.register(udp_socket)?;
+            println!("UDP Server is running on {}", addr);
         }
-        let acceptor_reactor = Reactor::new(2);
-        let udp_reactor = Reactor::new(2);
-        Server {
-            addr,
-            acceptor_reactor: Some(acceptor_reactor),
-            udp_reactor: Some(udp_reactor),
-            event_loop_thread_pool: Some(EventLoopThreadPool::new(num_threads)),
-            connection_callback: Some(connection_callback),
-            message_callback: Some(message_callback),
-            datagram_callback: Some(datagram_callback),
-            tcp: true,
-            udp: true,
-        }
-    }
-
-    fn get_acceptor_reactor(mut self) -> Reactor<Acceptor> {
-        self.event_loop_thread_pool.as_mut().unwrap().run();
-        let acceptor = Acceptor::new(
-            self.addr.clone(),
-            self.event_loop_thread_pool.take().unwrap(),
-            self.connection_callback.unwrap().clone(),
-            self.message_callback.unwrap().clone(),
-        );
-        self.acceptor_reactor.as_mut().unwrap().register(acceptor);
-        println!("TCP Server is running on {}", self.addr);
-        self.acceptor_reactor.unwrap()
-    }
-
-    fn get_udp_reactor(mut self) -> Reactor<UdpSocket> {
-        let signal_sender = self.udp_reactor.as_mut().unwrap().get_remote().get_sender();
-        let socket = mio::net::UdpSocket::bind(self.addr.parse().unwrap()).unwrap();
-        let udp_socket = UdpSocket::new(
-            socket,
-            self.datagram_callback.as_ref().unwrap().clone(),
-            signal_sender,
-        );
-        self.udp_reactor.as_mut().unwrap().register(udp_socket);
-        println!("UDP Server is running on {}", self.addr);
-        self.udp_reactor.unwrap()
-    }
-
-    pub fn run(mut self) {
-        if self.tcp && self.udp {
-            let signal_sender = self.udp_reactor.as_mut().unwrap().get_remote().get_sender();
-            let socket = mio::net::UdpSocket::bind(self.addr.parse().unwrap()).unwrap();
-            let udp_socket = UdpSocket::new(
-                socket,
-                self.datagram_callback.as_ref().unwrap().clone(),
-                signal_sender,
-            );
-            self.udp_reactor.as_mut().unwrap().register(udp_socket);
-            println!("UDP Server is running on {}", self.addr);
-            Self::run_reactor_in_thread(self.udp_reactor.unwrap());
-
-            self.event_loop_thread_pool.as_mut().unwrap().run();
-            let acceptor = Acceptor::new(
-                self.addr.clone(),
-                self.event_loop_thread_pool.take().unwrap(),
-                self.connection_callback.unwrap().clone(),
-                self.message_callback.unwrap().clone(),
-            );
-            self.acceptor_reactor.as_mut().unwrap().register(acceptor);
-            println!("TCP Server is running on {}", self.addr);
-            self.acceptor_reactor.unwrap().run();
-        } else if self.tcp {
-            self.get_acceptor_reactor().run();
-        } else if self.udp {
-            self.get_udp_reactor().run();
-        } else {
-            panic!("Invalid server status, must be tcp or udp or both");
+        Ok(Some(reactor))
+    }
+
+    // 在后台线程中运行所有 reactor，立即返回
+    pub fn start(mut self) -> Result<ServerHandle> {
+        let quiter = self.get_quiter();
+        // 全部注册成功后再启动线程，启动失败时不会遗留运行中的线程
+        let udp_reactor = self.get_udp_reactor()?;
+        let acceptor_reactor = self.get_acceptor_reactor()?;
+
+        let mut event_loop_thread_pool = self.event_loop_thread_pool.take();
+        if let Some(pool) = event_loop_thread_pool.as_mut() {
+            pool.run();
         }
+        Ok(ServerHandle {
+            local_addrs: self.local_addrs.clone(),
+            quiter,
+            acceptor_thread: acceptor_reactor.map(Self::run_reactor_in_thread),
+            udp_thread: udp_reactor.map(Self::run_reactor_in_thread),
+            event_loop_thread_pool,
+        })
+    }
+
+    // 注册失败等启动错误会返回 Err，成功启动后阻塞直到 quit
+    pub fn run(self) -> Result<()> {
+        self.start()?.join();
+        Ok(())
     }
 
-    fn run_reactor_in_thread<S>(reactor: Reactor<S>)
+    fn run_reactor_in_thread<S>(reactor: Reactor<S>) -> EventLoopThread<S>
     where
         S: ReactorSocket + 'static,
     {
         let mut event_loop_thread = EventLoopThread::with_reactor(reactor);
         event_loop_thread.run();
+        event_loop_thread
     }
 
     pub fn get_quiter(&self) -> ServerQuiter {
@@ -163,6 +455,48 @@ impl Server {
     }
 }
 
+pub struct ServerHandle {
+    local_addrs: LocalAddrs,
+    quiter: ServerQuiter,
+    acceptor_thread: Option<EventLoopThread<Acceptor>>,
+    udp_thread: Option<EventLoopThread<UdpSocket>>,
+    event_loop_thread_pool: Option<EventLoopThreadPool<TcpConnection>>,
+}
+
+impl ServerHandle {
+    pub fn local_addrs(&self) -> LocalAddrs {
+        self.local_addrs.clone()
+    }
+
+    // 可以交给其他线程用来停止服务器
+    pub fn get_quiter(&self) -> ServerQuiter {
+        self.quiter.clone()
+    }
+
+    pub fn shutdown(&self) {
+        self.quiter.quit();
+    }
+
+    // 停止接受新连接，等待已有连接发送完毕后退出，最多等待 timeout
+    pub fn drain(&self, timeout: Duration) {
+        self.quiter.drain(timeout);
+    }
+
+    // 等待所有 reactor 线程退出
+    pub fn join(self) {
+        if let Some(thread) = self.acceptor_thread {
+            thread.wait();
+        }
+        if let Some(thread) = self.udp_thread {
+            thread.wait();
+        }
+        if let Some(pool) = self.event_loop_thread_pool {
+            pool.wait();
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ServerQuiter {
     acceptor_remote: Option<ReactorRemote<Acceptor>>,
     tcp_remotes: Option<Vec<ReactorRemote<TcpConnection>>>,
@@ -195,11 +529,36 @@ impl ServerQuiter {
             remote.quit();
         }
     }
+
+    // 先注销 Acceptor 停止接受新连接，再优雅关闭所有连接。
+    // 超时仍未关闭的连接被强制关闭，每个连接都会收到 ConnectionCallback(conn, false)
+    pub fn drain(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let tcp_remotes = self.tcp_remotes.clone().unwrap_or_default();
+        let drain_io = move || {
+            for remote in &tcp_remotes {
+                remote.drain(deadline.saturating_duration_since(Instant::now()));
+            }
+        };
+        match &self.acceptor_remote {
+            // 在 acceptor 的 loop 中注销所有 Acceptor 之后再排空 io reactor，之后不会再有新连接。
+            // 此前接受的连接的 Register 信号排在 drain 之前，注册后同样会被优雅关闭
+            Some(remote) => remote.run_in_loop(move |reactor| {
+                reactor.drain(Duration::ZERO);
+                drain_io();
+            }),
+            None => drain_io(),
+        }
+        if let Some(remote) = &self.udp_remote {
+            remote.drain(Duration::ZERO);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
+        io::{Read, Write},
         sync::Arc,
         thread::{self, sleep},
         time::Duration,
@@ -207,7 +566,7 @@ mod tests {
 
     use log::info;
 
-    use crate::{Server, TcpConnection};
+    use crate::{Error, Server, TcpConnection};
 
     fn message_callback(
         remote: Arc<crate::SocketRemote<TcpConnection>>,
@@ -243,58 +602,324 @@ mod tests {
     }
 
     fn run_tcp_server_one_sec_and_quit() {
-        let addr = "127.0.0.1:8888".to_string();
-        let server = Server::tcp_server(
-            addr.clone(),
-            4,
-            Arc::new(message_callback),
-            Arc::new(connection_callback),
-        );
+        let addr = "127.0.0.1:0".to_string();
+        let server = Server::builder()
+            .tcp(addr.clone())
+            .io_threads(4)
+            .message_callback(Arc::new(message_callback))
+            .connection_callback(Arc::new(connection_callback))
+            .build()
+            .unwrap();
         let quiter = server.get_quiter();
 
         let tid = thread::spawn(move || {
             sleep(Duration::from_millis(1000));
             quiter.quit();
         });
-        server.run();
+        server.run().unwrap();
 
         tid.join().expect("fail to wait thread");
     }
 
     fn run_udp_server_one_sec_and_quit() {
-        let addr = "127.0.0.1:8888".to_string();
-        let server = Server::udp_server(addr.clone(), Arc::new(datagram_callback));
+        let addr = "127.0.0.1:0".to_string();
+        let server = Server::builder()
+            .udp(addr.clone())
+            .datagram_callback(Arc::new(datagram_callback))
+            .build()
+            .unwrap();
+        assert!(server.local_addrs().tcp.is_empty());
+        assert_ne!(server.local_addrs().udp[0].port(), 0);
         let quiter = server.get_quiter();
 
         let tid = thread::spawn(move || {
             sleep(Duration::from_millis(1000));
             quiter.quit();
         });
-        server.run();
+        server.run().unwrap();
 
         tid.join().expect("fail to wait thread");
     }
 
     fn run_tcp_udp_server_one_sec_and_quit() {
-        let addr = "127.0.0.1:8888".to_string();
-        let server = Server::tcp_udp_server(
-            addr.clone(),
-            4,
-            Arc::new(message_callback),
-            Arc::new(connection_callback),
-            Arc::new(datagram_callback),
-        );
+        let addr = "127.0.0.1:0".to_string();
+        let server = Server::builder()
+            .tcp(addr.clone())
+            .udp(addr.clone())
+            .io_threads(4)
+            .message_callback(Arc::new(message_callback))
+            .connection_callback(Arc::new(connection_callback))
+            .datagram_callback(Arc::new(datagram_callback))
+            .build()
+            .unwrap();
+        let local_addrs = server.local_addrs();
+        assert_ne!(local_addrs.tcp[0].port(), 0);
+        assert_ne!(local_addrs.udp[0].port(), 0);
         let quiter = server.get_quiter();
 
         let tid = thread::spawn(move || {
             sleep(Duration::from_millis(1000));
             quiter.quit();
         });
-        server.run();
+        server.run().unwrap();
 
         tid.join().expect("fail to wait thread");
     }
 
+    #[test]
+    fn test_builder_validation() {
+        assert!(matches!(
+            Server::builder().build(),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Server::builder().tcp("not an address").build(),
+            Err(Error::AddrParse(_, _))
+        ));
+        assert!(matches!(
+            Server::builder().udp("127.0.0.1:0").build(),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Server::builder().tcp("127.0.0.1:0").io_threads(0).build(),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Server::builder()
+                .tcp("127.0.0.1:0")
+                .listen_backlog(0)
+                .build(),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Server::builder()
+                .tcp("127.0.0.1:0")
+                .idle_callback(Arc::new(|_| {}))
+                .build(),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        // 只对 TCP 生效的选项在没有 TCP 地址时分别报错
+        let udp_only = || {
+            Server::builder()
+                .udp("127.0.0.1:0")
+                .datagram_callback(Arc::new(|_, _, _, _| {}))
+        };
+        let config_error = |result: crate::error::Result<Server>| match result {
+            Err(Error::InvalidConfig(msg)) => msg,
+            _ => panic!("expected an invalid config error"),
+        };
+        assert_eq!(
+            config_error(udp_only().message_callback(Arc::new(|_, _, _| {})).build()),
+            "tcp callbacks are set without a tcp address"
+        );
+        assert_eq!(
+            config_error(udp_only().tcp_options(crate::TcpOptions::new()).build()),
+            "tcp_options is set without a tcp address"
+        );
+        assert_eq!(
+            config_error(udp_only().idle_timeout(Duration::from_secs(1)).build()),
+            "idle_timeout is set without a tcp address"
+        );
+        assert_eq!(
+            config_error(udp_only().max_input_buffer(1024).build()),
+            "max_input_buffer is set without a tcp address"
+        );
+        assert!(udp_only().build().is_ok());
+        assert!(
+            Server::builder()
+                .tcp("127.0.0.1:0")
+                .io_threads(2)
+                .sock_capacity(16)
+                .events_capacity(16)
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_startup_errors() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let server = Server::builder().tcp(addr.clone()).build();
+        assert!(matches!(server, Err(Error::Io(_))));
+
+        // 连接被拒绝由 Connector 的错误回调报告，这里只有地址错误
+        let client = crate::Client::<TcpConnection>::new(
+            "not an addr".to_string(),
+            Arc::new(message_callback),
+            Arc::new(connection_callback),
+        );
+        assert!(matches!(client, Err(Error::AddrParse(..))));
+    }
+
+    #[test]
+    fn test_start_and_join() {
+        let handle = Server::builder()
+            .tcp("127.0.0.1:0")
+            .io_threads(2)
+            .message_callback(Arc::new(|conn, buffer, _| {
+                conn.write(buffer.as_slice());
+                buffer.retrieve_all();
+            }))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap();
+
+        let mut stream = std::net::TcpStream::connect(handle.local_addrs().tcp[0]).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        stream.write_all(b"hello").unwrap();
+        let mut reply = [0; 5];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"hello");
+
+        handle.shutdown();
+        handle.join();
+    }
+
+    #[test]
+    fn test_multiple_addrs() {
+        let handle = Server::builder()
+            .tcp("127.0.0.1:0")
+            .tcp("127.0.0.1:0")
+            .udp("127.0.0.1:0")
+            .udp("127.0.0.1:0")
+            .io_threads(1)
+            .message_callback(Arc::new(|conn, buffer, _| {
+                conn.write(buffer.as_slice());
+                buffer.retrieve_all();
+            }))
+            .datagram_callback(Arc::new(|conn, data, addr, _| {
+                conn.send(addr, data);
+            }))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap();
+        let local_addrs = handle.local_addrs();
+        assert_eq!(local_addrs.tcp.len(), 2);
+        assert_eq!(local_addrs.udp.len(), 2);
+        assert_ne!(local_addrs.tcp[0], local_addrs.tcp[1]);
+
+        for addr in &local_addrs.tcp {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .unwrap();
+            stream.write_all(b"hello").unwrap();
+            let mut reply = [0; 5];
+            stream.read_exact(&mut reply).unwrap();
+            assert_eq!(&reply, b"hello");
+        }
+        for addr in &local_addrs.udp {
+            let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .unwrap();
+            socket.send_to(b"ping", addr).unwrap();
+            let mut reply = [0; 4];
+            let (n, from) = socket.recv_from(&mut reply).unwrap();
+            assert_eq!((&reply[..n], from), (&b"ping"[..], *addr));
+        }
+
+        // 排空时注销所有 Acceptor
+        handle.drain(Duration::from_millis(100));
+        handle.join();
+        for addr in &local_addrs.tcp {
+            assert!(std::net::TcpStream::connect(addr).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tcp_options() {
+        let (conn_tx, conn_rx) = std::sync::mpsc::channel();
+        let conn_tx = std::sync::Mutex::new(conn_tx);
+        let handle = Server::builder()
+            .tcp("127.0.0.1:0")
+            .io_threads(1)
+            .tcp_options(crate::TcpOptions::new().nodelay(true))
+            .connection_callback(Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn_tx.lock().unwrap().send(conn).unwrap();
+                }
+            }))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap();
+
+        let _stream = std::net::TcpStream::connect(handle.local_addrs().tcp[0]).unwrap();
+        let conn = conn_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        let nodelay = |conn: &Arc<crate::SocketRemote<TcpConnection>>| {
+            let (tx, rx) = std::sync::mpsc::channel();
+            conn.run_in_loop(move |conn| {
+                use crate::ReactorSocket;
+                let socket = socket2::SockRef::from(&*conn.socket());
+                tx.send(socket.tcp_nodelay().unwrap()).unwrap();
+            });
+            rx.recv_timeout(Duration::from_secs(1)).unwrap()
+        };
+        assert!(nodelay(&conn));
+
+        conn.set_nodelay(false);
+        assert!(!nodelay(&conn));
+
+        handle.shutdown();
+        handle.join();
+    }
+
+    #[test]
+    fn test_drain() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        let handle = Server::builder()
+            .tcp("127.0.0.1:0")
+            .io_threads(2)
+            .connection_callback(Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn.write(b"hi");
+                }
+                tx.lock().unwrap().send(is_connected).unwrap();
+            }))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap();
+        let addr = handle.local_addrs().tcp[0];
+
+        let mut polite = std::net::TcpStream::connect(addr).unwrap();
+        let stubborn = std::net::TcpStream::connect(addr).unwrap();
+        for _ in 0..2 {
+            assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+        }
+
+        let start = std::time::Instant::now();
+        handle.drain(Duration::from_millis(300));
+
+        // 已写入的数据先发送完毕，随后收到 FIN
+        let mut received = Vec::new();
+        polite
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        polite.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"hi");
+        drop(polite);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        // 不主动关闭的连接在超时后被强制关闭
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        handle.join();
+
+        assert!(std::net::TcpStream::connect(addr).is_err());
+        drop(stubborn);
+    }
+
     #[test]
     fn test_server() {
         env_logger::Builder::from_default_env()
@@ -1,10 +1,20 @@
 use std::{
+    any::Any,
+    fs::File,
+    io,
     net::SocketAddr,
-    sync::{Arc, atomic::AtomicBool},
+    path::Path,
+    sync::{Arc, Mutex, atomic::AtomicBool},
 };
 
+use bytes::Bytes;
+use log::warn;
+
 use crate::{
-    ReactorSocket, TcpConnection, UdpSocket, reactor::ReactorSignal, reactor_channel::Sender,
+    Error, Keepalive, ReactorSocket, TcpConnection, TcpOptions, UdpSocket,
+    error::Result,
+    output_queue::FileRegion,
+    socket_sender::{SocketSender, SocketSignal},
 };
 
 pub struct SocketRemote<S>
@@ -14,19 +24,23 @@ where
     local_addr: SocketAddr,
     peer_addr: SocketAddr,
     poll_token: mio::Token,
-    sender: Sender<ReactorSignal<S>>,
+    sender: SocketSender<S>,
     is_established: Arc<AtomicBool>,
+    // 用户数据，每种类型最多一份
+    context: Mutex<Vec<Arc<dyn Any + Send + Sync>>>,
+    // 分帧层的编解码器，和用户数据分开保存，不受 clear_context 影响
+    codec_state: Mutex<Vec<Arc<dyn Any + Send + Sync>>>,
 }
 
 impl<S> SocketRemote<S>
 where
-    S: ReactorSocket,
+    S: ReactorSocket + 'static,
 {
     pub fn new(
         local_addr: SocketAddr,
         peer_addr: SocketAddr,
         poll_token: mio::Token,
-        sender: Sender<ReactorSignal<S>>,
+        sender: SocketSender<S>,
         is_established: Arc<AtomicBool>,
     ) -> Self {
         SocketRemote {
@@ -35,6 +49,8 @@ where
             poll_token,
             sender,
             is_established,
+            context: Mutex::new(Vec::new()),
+            codec_state: Mutex::new(Vec::new()),
         }
     }
     pub fn local_addr(&self) -> SocketAddr {
@@ -43,38 +59,241 @@ where
     pub fn peer_addr(&self) -> SocketAddr {
         self.peer_addr
     }
+    // TCP 连接会先发送完输出缓冲区再半关闭写端，等待对端关闭
     pub fn shutdown(&self) {
-        self.sender.send(ReactorSignal::ShutDown(self.poll_token));
+        self.sender.send(SocketSignal::ShutDown(self.poll_token));
+    }
+
+    // 丢弃未发送的数据，立即关闭
+    pub fn force_close(&self) {
+        self.sender.send(SocketSignal::ForceClose(self.poll_token));
     }
     pub fn reregister(&self, interest: mio::Interest) {
         self.sender
-            .send(ReactorSignal::ReRegister(self.poll_token, interest));
+            .send(SocketSignal::ReRegister(self.poll_token, interest));
     }
 
     pub fn is_established(&self) -> bool {
         self.is_established
             .load(std::sync::atomic::Ordering::Relaxed)
     }
+
+    // 每个连接一份的用户数据，连接关闭时释放。需要修改时用 Mutex 等包装。
+    // 同一类型只保存一份，再次设置时替换旧值
+    pub fn set_context<T>(&self, context: T)
+    where
+        T: Any + Send + Sync,
+    {
+        set_typed(&self.context, context);
+    }
+
+    // 未设置该类型时返回 None
+    pub fn context<T>(&self) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        get_typed(&self.context)
+    }
+
+    // 清除所有类型的数据
+    pub fn clear_context(&self) {
+        self.context.lock().unwrap().clear();
+    }
+
+    pub(crate) fn set_codec_state<T>(&self, state: T)
+    where
+        T: Any + Send + Sync,
+    {
+        set_typed(&self.codec_state, state);
+    }
+
+    pub(crate) fn codec_state<T>(&self) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        get_typed(&self.codec_state)
+    }
+
+    // 在 socket 所属的 loop 线程中操作 socket，socket 已关闭时不执行。
+    // 持有 &mut Reactor 时可以用 socket_mut 直接操作；在回调中 socket 正被借用，functor 在回调返回后执行
+    pub fn run_in_loop<F>(&self, functor: F)
+    where
+        F: FnOnce(&mut S) + Send + 'static,
+    {
+        self.sender.run_in_loop(self.poll_token, functor);
+    }
+
+    pub fn is_in_loop_thread(&self) -> bool {
+        self.sender.is_in_loop_thread()
+    }
+
+    #[track_caller]
+    pub fn assert_in_loop_thread(&self) {
+        self.sender.assert_in_loop_thread();
+    }
 }
 
 impl SocketRemote<TcpConnection> {
     pub fn write(&self, data: &[u8]) -> bool {
+        self.try_write(data).is_ok()
+    }
+
+    // 连接未建立时返回 NotConnected，信号队列已满且策略为 Reject 时返回 QueueFull
+    pub fn try_write(&self, data: &[u8]) -> Result<()> {
+        self.try_write_vectored(vec![Bytes::copy_from_slice(data)])
+    }
+
+    // 数据交给连接后不再复制，直到被 writev 写入 socket
+    pub fn write_bytes(&self, data: Bytes) -> bool {
+        self.try_write_vectored(vec![data]).is_ok()
+    }
+
+    // 多个数据块按顺序发送，例如协议头和大块的消息体
+    pub fn write_vectored(&self, chunks: Vec<Bytes>) -> bool {
+        self.try_write_vectored(chunks).is_ok()
+    }
+
+    pub fn try_write_vectored(&self, chunks: Vec<Bytes>) -> Result<()> {
         if !self.is_established() {
-            return false;
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
         }
-        self.sender
-            .send(ReactorSignal::Write(self.poll_token, data.to_vec()));
-        true
+        if !self
+            .sender
+            .try_send(SocketSignal::Write(self.poll_token, chunks))
+        {
+            return Err(Error::QueueFull);
+        }
+        Ok(())
+    }
+
+    // 发送文件中从 offset 开始的 len 个字节，len 为 None 时发送到文件末尾。
+    // 文件区间与之前和之后的写入按顺序发送，全部发送完后触发 write complete 回调
+    pub fn send_file(
+        &self,
+        file: impl Into<Arc<File>>,
+        offset: u64,
+        len: Option<u64>,
+    ) -> Result<()> {
+        if !self.is_established() {
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
+        }
+        let file = file.into();
+        let file_len = file.metadata()?.len();
+        let len = match len {
+            Some(len) => len,
+            None => file_len.saturating_sub(offset),
+        };
+        if offset.checked_add(len).is_none_or(|end| end > file_len) {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "file region is out of range").into(),
+            );
+        }
+        let region = FileRegion::new(file, offset, len);
+        self.run_in_loop(move |conn| conn.send_file(region));
+        Ok(())
+    }
+
+    pub fn send_file_path(
+        &self,
+        path: impl AsRef<Path>,
+        offset: u64,
+        len: Option<u64>,
+    ) -> Result<()> {
+        self.send_file(File::open(path)?, offset, len)
+    }
+
+    pub fn stop_read(&self) {
+        self.run_in_loop(|conn| conn.stop_read());
+    }
+
+    pub fn start_read(&self) {
+        self.run_in_loop(|conn| conn.start_read());
+    }
+
+    // 在 loop 线程中设置 socket 选项，失败时只记录日志
+    pub fn set_tcp_options(&self, options: TcpOptions) {
+        self.run_in_loop(move |conn| {
+            if let Err(e) = conn.set_tcp_options(&options) {
+                warn!("Failed to set tcp options {:?}: {}", options, e);
+            }
+        });
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) {
+        self.set_tcp_options(TcpOptions::new().nodelay(nodelay));
+    }
+
+    pub fn set_keepalive(&self, keepalive: Option<Keepalive>) {
+        self.set_tcp_options(TcpOptions::new().keepalive(keepalive));
     }
 }
 
 impl SocketRemote<UdpSocket> {
     pub fn send(&self, addr: SocketAddr, data: &[u8]) -> bool {
+        self.try_send(addr, data).is_ok()
+    }
+
+    pub fn try_send(&self, addr: SocketAddr, data: &[u8]) -> Result<()> {
         if !self.is_established() {
-            return false;
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
         }
-        self.sender
-            .send(ReactorSignal::Send(self.poll_token, addr, data.to_vec()));
-        true
+        if !self
+            .sender
+            .try_send(SocketSignal::Send(self.poll_token, addr, data.to_vec()))
+        {
+            return Err(Error::QueueFull);
+        }
+        Ok(())
+    }
+}
+
+// 同一类型只保存一份，再次设置时替换旧值
+fn set_typed<T>(store: &Mutex<Vec<Arc<dyn Any + Send + Sync>>>, value: T)
+where
+    T: Any + Send + Sync,
+{
+    let mut values = store.lock().unwrap();
+    values.retain(|v| !v.is::<T>());
+    values.push(Arc::new(value));
+}
+
+fn get_typed<T>(store: &Mutex<Vec<Arc<dyn Any + Send + Sync>>>) -> Option<Arc<T>>
+where
+    T: Any + Send + Sync,
+{
+    let value = store.lock().unwrap().iter().find(|v| v.is::<T>())?.clone();
+    value.downcast::<T>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Reactor, UdpSocket};
+
+    #[test]
+    fn test_context() {
+        let mut reactor = Reactor::<UdpSocket>::new(2).unwrap();
+        let socket = mio::net::UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let socket = UdpSocket::new(socket, Arc::new(|_, _, _, _| {}), reactor.get_sender());
+        let token = reactor.register(socket).unwrap();
+        let remote = reactor.socket(token).unwrap().remote().clone();
+
+        assert!(remote.context::<Mutex<u32>>().is_none());
+        remote.set_context(Mutex::new(1u32));
+        *remote.context::<Mutex<u32>>().unwrap().lock().unwrap() += 1;
+        assert_eq!(*remote.context::<Mutex<u32>>().unwrap().lock().unwrap(), 2);
+        assert!(remote.context::<String>().is_none());
+
+        // 不同类型的数据互不覆盖
+        remote.set_context(String::from("user"));
+        assert_eq!(*remote.context::<Mutex<u32>>().unwrap().lock().unwrap(), 2);
+        assert_eq!(remote.context::<String>().unwrap().as_str(), "user");
+
+        let session = Arc::new(());
+        remote.set_context(session.clone());
+        assert_eq!(Arc::strong_count(&session), 2);
+        reactor.close(token);
+        assert_eq!(Arc::strong_count(&session), 1);
     }
 }
@@ -0,0 +1,142 @@
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+
+use bytes::Bytes;
+use mio::{Interest, Token};
+
+use crate::{
+    reactor::ReactorSignal,
+    reactor_channel::Sender,
+    reactor_socket::AsSocket,
+    timer_queue::{TimerCallback, TimerId},
+};
+
+// 与 reactor 中 socket 类型无关的信号
+pub enum SocketSignal {
+    ShutDown(Token),
+    ForceClose(Token),
+    ReRegister(Token, Interest),
+    SyncInterest(Token),
+    Write(Token, Vec<Bytes>),
+    Send(Token, SocketAddr, Vec<u8>),
+    AddTimer(TimerId, Instant, TimerCallback),
+    CancelTimer(TimerId),
+}
+
+impl<H> From<SocketSignal> for ReactorSignal<H>
+where
+    H: crate::ReactorSocket,
+{
+    fn from(signal: SocketSignal) -> Self {
+        match signal {
+            SocketSignal::ShutDown(token) => Self::ShutDown(token),
+            SocketSignal::ForceClose(token) => Self::ForceClose(token),
+            SocketSignal::ReRegister(token, interest) => Self::ReRegister(token, interest),
+            SocketSignal::SyncInterest(token) => Self::SyncInterest(token),
+            SocketSignal::Write(token, data) => Self::Write(token, data),
+            SocketSignal::Send(token, addr, data) => Self::Send(token, addr, data),
+            SocketSignal::AddTimer(id, when, callback) => Self::AddTimer(id, when, callback),
+            SocketSignal::CancelTimer(id) => Self::CancelTimer(id),
+        }
+    }
+}
+
+trait SignalSink<S>: Send + Sync {
+    fn send(&self, signal: SocketSignal);
+    fn try_send(&self, signal: SocketSignal) -> bool;
+    fn run_in_loop(&self, token: Token, functor: Box<dyn FnOnce(&mut S) + Send>);
+    fn is_in_loop_thread(&self) -> bool;
+}
+
+impl<H, S> SignalSink<S> for Sender<ReactorSignal<H>>
+where
+    H: AsSocket<S> + 'static,
+    S: 'static,
+{
+    fn send(&self, signal: SocketSignal) {
+        Sender::send(self, signal.into());
+    }
+
+    fn try_send(&self, signal: SocketSignal) -> bool {
+        // 只有数据报可以被 DropOldest 丢弃，TCP 字节流不能缺少中间的数据
+        if matches!(signal, SocketSignal::Send(..)) {
+            Sender::try_send_droppable(self, signal.into()).is_ok()
+        } else {
+            Sender::try_send(self, signal.into()).is_ok()
+        }
+    }
+
+    fn run_in_loop(&self, token: Token, functor: Box<dyn FnOnce(&mut S) + Send>) {
+        Sender::send(
+            self,
+            ReactorSignal::Functor(Box::new(move |reactor| {
+                if let Some(socket) = reactor.socket_mut(token).and_then(H::as_socket_mut) {
+                    functor(socket);
+                }
+            })),
+        );
+    }
+
+    fn is_in_loop_thread(&self) -> bool {
+        Sender::is_in_loop_thread(self)
+    }
+}
+
+// socket 用来给所在 reactor 发信号。擦除了 reactor 的类型，
+// 同一种 socket 既可以注册到 Reactor<S>，也可以注册到混合多种 socket 的 reactor
+pub struct SocketSender<S> {
+    sink: Arc<dyn SignalSink<S>>,
+}
+
+impl<S> SocketSender<S>
+where
+    S: 'static,
+{
+    pub fn send(&self, signal: SocketSignal) {
+        self.sink.send(signal);
+    }
+
+    // 写入等数据信号受 reactor 信号队列容量限制，被拒绝时返回 false
+    pub fn try_send(&self, signal: SocketSignal) -> bool {
+        self.sink.try_send(signal)
+    }
+
+    // socket 已关闭或 Token 失效时不执行
+    pub fn run_in_loop<F>(&self, token: Token, functor: F)
+    where
+        F: FnOnce(&mut S) + Send + 'static,
+    {
+        self.sink.run_in_loop(token, Box::new(functor));
+    }
+
+    pub fn is_in_loop_thread(&self) -> bool {
+        self.sink.is_in_loop_thread()
+    }
+
+    #[track_caller]
+    pub fn assert_in_loop_thread(&self) {
+        debug_assert!(
+            self.is_in_loop_thread(),
+            "must be called in the reactor's loop thread"
+        );
+    }
+}
+
+impl<S> Clone for SocketSender<S> {
+    fn clone(&self) -> Self {
+        SocketSender {
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+impl<H, S> From<Sender<ReactorSignal<H>>> for SocketSender<S>
+where
+    H: AsSocket<S> + 'static,
+    S: 'static,
+{
+    fn from(sender: Sender<ReactorSignal<H>>) -> Self {
+        SocketSender {
+            sink: Arc::new(sender),
+        }
+    }
+}
@@ -1,28 +1,63 @@
 use std::{
-    io::Write,
+    io::{IoSlice, Write},
+    net::{Shutdown, SocketAddr},
     sync::{Arc, atomic::AtomicBool},
+    time::{Duration, Instant},
 };
 
-use log::{error, trace, warn};
+use bytes::Bytes;
+use log::{debug, error, trace, warn};
 use mio::{Interest, net::TcpStream};
 
 use crate::{
-    Buffer, ReactorSocket, SocketRemote,
-    callbacks::{ConnectionCallback, MessageCallback},
-    reactor::ReactorSignal,
-    reactor_channel::Sender,
+    Buffer, ReactorSocket, SocketRemote, TcpOptions, buffer,
+    callbacks::{
+        ConnectionCallback, HighWaterMarkCallback, IdleCallback, MessageCallback,
+        WriteCompleteCallback,
+    },
+    connector::{self, Connecting},
+    output_queue::{FileRegion, OutputQueue},
+    socket_sender::{SocketSender, SocketSignal},
+    timer_queue::{TimerCallback, TimerId},
 };
 
+const DEFAULT_HIGH_WATER_MARK: usize = 64 * 1024 * 1024;
+// 暂停读取且没有待发送数据时注册的 interest。mio 不能注册空的 interest，
+// Linux 上注册 PRIORITY 不会产生普通的读事件，对端重置时仍会收到 EPOLLERR/EPOLLHUP
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const PAUSED_INTEREST: Interest = Interest::PRIORITY;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const PAUSED_INTEREST: Interest = Interest::READABLE;
+// 输入缓冲区超过这个大小时，空闲一段时间后释放多余的空间
+const SHRINK_THRESHOLD: usize = 64 * 1024;
+const DEFAULT_SHRINK_DELAY: Duration = Duration::from_secs(30);
+
 pub struct TcpConnection {
     stream: TcpStream,
     connection_callback: ConnectionCallback,
     message_callback: MessageCallback,
+    write_complete_callback: Option<WriteCompleteCallback>,
+    high_water_mark_callback: Option<HighWaterMarkCallback>,
+    high_water_mark: usize,
+    max_input_buffer: usize,
+    idle_timeout: Option<Duration>,
+    idle_callback: Option<IdleCallback>,
+    idle_timer: TimerId,
+    last_active: Instant,
+    shrink_delay: Option<Duration>,
+    shrink_timer: TimerId,
+    shrink_timer_armed: bool,
+    reading: bool,
     input_buffer: Buffer,
-    output_buffer: Buffer,
-    signal_sender: Sender<ReactorSignal<TcpConnection>>,
+    output: OutputQueue,
+    signal_sender: SocketSender<TcpConnection>,
     remote: Option<Arc<SocketRemote<TcpConnection>>>,
     interest: mio::Interest,
     poll_token: Option<mio::Token>,
+    disconnecting: bool,
+    // 读到了对端的 EOF，发送完剩余的数据后关闭连接
+    peer_closed: bool,
+    connecting: Option<Connecting>,
     pub is_established: Arc<AtomicBool>,
 }
 
@@ -32,22 +67,129 @@ impl TcpConnection {
         connection_callback: ConnectionCallback,
         message_callback: MessageCallback,
         interest: mio::Interest,
-        signal_sender: Sender<ReactorSignal<TcpConnection>>,
+        signal_sender: impl Into<SocketSender<TcpConnection>>,
     ) -> Self {
         TcpConnection {
             stream,
             connection_callback,
             message_callback,
+            write_complete_callback: None,
+            high_water_mark_callback: None,
+            high_water_mark: DEFAULT_HIGH_WATER_MARK,
+            max_input_buffer: usize::MAX,
+            idle_timeout: None,
+            idle_callback: None,
+            idle_timer: TimerId::next(),
+            last_active: Instant::now(),
+            shrink_delay: Some(DEFAULT_SHRINK_DELAY),
+            shrink_timer: TimerId::next(),
+            shrink_timer_armed: false,
+            reading: true,
             input_buffer: Buffer::new(),
-            output_buffer: Buffer::new(),
-            signal_sender,
+            output: OutputQueue::new(),
+            signal_sender: signal_sender.into(),
             remote: None,
             interest,
             poll_token: None,
+            disconnecting: false,
+            peer_closed: false,
+            connecting: None,
             is_established: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    pub fn set_write_complete_callback(&mut self, callback: WriteCompleteCallback) {
+        self.write_complete_callback = Some(callback);
+    }
+
+    // 待发送数据从低于 high_water_mark 增长到不低于它时触发
+    pub fn set_high_water_mark_callback(
+        &mut self,
+        callback: HighWaterMarkCallback,
+        high_water_mark: usize,
+    ) {
+        self.high_water_mark_callback = Some(callback);
+        self.high_water_mark = high_water_mark;
+    }
+
+    // 输入缓冲区达到上限且消息回调没有消费时自动暂停读取，需调用 start_read 恢复
+    pub fn set_max_input_buffer(&mut self, max_input_buffer: usize) {
+        self.max_input_buffer = max_input_buffer;
+    }
+
+    // 超过 idle_timeout 没有读写时关闭连接，设置了 idle_callback 时改为调用回调
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = Some(idle_timeout);
+    }
+
+    // 回调中可以发送心跳，写入会刷新活跃时间；没有读写时每隔 idle_timeout 调用一次
+    pub fn set_idle_callback(&mut self, callback: IdleCallback) {
+        self.idle_callback = Some(callback);
+    }
+
+    // 突发的大量数据使缓冲区变大后，超过 delay 没有读写时释放多余的空间，None 表示不释放
+    pub fn set_buffer_shrink_delay(&mut self, delay: Option<Duration>) {
+        self.shrink_delay = delay;
+    }
+
+    pub fn set_tcp_options(&self, options: &TcpOptions) -> std::io::Result<()> {
+        options.apply(&self.stream)
+    }
+
+    pub fn last_active(&self) -> Instant {
+        self.last_active
+    }
+
+    // 暂停读取：从 reactor 的 interest 中去掉 READABLE，保留 WRITABLE。
+    // 未读的数据留在内核缓冲区，由 TCP 窗口反压对端
+    pub fn stop_read(&mut self) {
+        if self.reading {
+            self.reading = false;
+            let interest = if self.interest.is_writable() {
+                Interest::WRITABLE
+            } else {
+                PAUSED_INTEREST
+            };
+            self.update_interest(interest);
+        }
+    }
+
+    // 恢复读取，暂停期间到达的数据不会再产生事件，所以立即读一次
+    pub fn start_read(&mut self) {
+        if !self.reading {
+            self.reading = true;
+            let interest = if self.interest.is_writable() {
+                Interest::READABLE | Interest::WRITABLE
+            } else {
+                Interest::READABLE
+            };
+            self.update_interest(interest);
+            self.handle_read(Instant::now());
+        }
+    }
+
+    // 立即更新 interest，之后到达的写入信号据此判断是否有待发送的数据；注册前只记录。
+    // reactor 处理信号时按那时的 interest 注册，不会用排队期间过时的值覆盖
+    fn update_interest(&mut self, interest: Interest) {
+        self.interest = interest;
+        if let Some(token) = self.poll_token {
+            self.signal_sender.send(SocketSignal::SyncInterest(token));
+        }
+    }
+
+    pub fn is_reading(&self) -> bool {
+        self.reading
+    }
+
+    // 由 Connector 设置，注册后等待可写，连接完成前不调用 connection_callback
+    pub(crate) fn set_connecting(&mut self, connecting: Connecting) {
+        self.connecting = Some(connecting);
+    }
+
+    pub fn is_connecting(&self) -> bool {
+        self.connecting.is_some()
+    }
+
     // must call after register
     pub fn remote(&self) -> &Arc<SocketRemote<TcpConnection>> {
         self.remote
@@ -55,16 +197,66 @@ impl TcpConnection {
             .expect("must call register before accessing remote")
     }
 
-    fn handle_read(&mut self, receive_time: std::time::Instant) {
+    // 文件区间排在已有的待发送数据之后，socket 可写时由 handle_write 用 sendfile 发送
+    pub fn send_file(&mut self, region: FileRegion) {
+        if self.disconnecting {
+            warn!(
+                "Drop file region of {} bytes sent after shutdown: {:?}",
+                region.len(),
+                self.poll_token
+            );
+            return;
+        }
+        if region.is_empty() {
+            return;
+        }
+        self.push_output(|output| output.push_file(region));
+        if !self.interest.is_writable() {
+            // 立即标记为等待可写，之后到达的写入会排在文件之后
+            self.update_interest(self.interest.add(Interest::WRITABLE));
+        }
+    }
+
+    fn push_output(&mut self, push: impl FnOnce(&mut OutputQueue)) {
+        let old_len = self.output.len();
+        push(&mut self.output);
+        let new_len = self.output.len();
+        if old_len < self.high_water_mark
+            && new_len >= self.high_water_mark
+            && let Some(callback) = &self.high_water_mark_callback
+        {
+            callback(self.remote().clone(), new_len);
+        }
+    }
+
+    fn handle_read(&mut self, receive_time: Instant) {
+        if self.peer_closed {
+            return;
+        }
         let mut total_read = 0;
-        loop {
-            match self.input_buffer.read_tcp_stream(&mut self.stream) {
+        while self.reading {
+            if self.input_buffer.readable_bytes() >= self.max_input_buffer {
+                // 先交给用户消费，仍然超限则暂停读取，剩余数据留在内核缓冲区
+                total_read = 0;
+                (self.message_callback)(
+                    self.remote().clone(),
+                    &mut self.input_buffer,
+                    receive_time,
+                );
+                if self.input_buffer.readable_bytes() >= self.max_input_buffer {
+                    debug!(
+                        "Input buffer of {} reaches {} bytes, stop reading",
+                        self.remote().peer_addr(),
+                        self.input_buffer.readable_bytes()
+                    );
+                    self.stop_read();
+                    break;
+                }
+            }
+            match self.input_buffer.read_from(&mut self.stream) {
                 Ok(bytes_read) => {
                     if bytes_read == 0 {
-                        trace!(
-                            "Connection closed by peer: {}",
-                            self.stream.peer_addr().unwrap()
-                        );
+                        trace!("Connection closed by peer: {}", self.remote().peer_addr());
                         if total_read > 0 {
                             (self.message_callback)(
                                 self.remote().clone(),
@@ -72,17 +264,20 @@ impl TcpConnection {
                                 receive_time,
                             );
                         }
+                        // 对端可能只是半关闭，排在已提交的写入之后优雅关闭，发送完剩余的数据再关闭
+                        self.peer_closed = true;
                         self.remote().shutdown();
                         return;
                     }
                     total_read += bytes_read;
+                    self.last_active = receive_time;
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     break;
                 }
                 Err(e) => {
                     warn!("Failed to read from stream: {}, shutdown it", e);
-                    self.remote().shutdown();
+                    self.remote().force_close();
                     return;
                 }
             }
@@ -91,47 +286,188 @@ impl TcpConnection {
         if total_read > 0 {
             (self.message_callback)(self.remote().clone(), &mut self.input_buffer, receive_time);
         }
+        if self.input_buffer.capacity() > SHRINK_THRESHOLD {
+            self.schedule_shrink();
+        }
     }
     fn handle_write(&mut self) {
-        let data = self.output_buffer.as_slice();
         let mut total_written = 0;
-        if total_written < data.len() {
-            loop {
-                match self.stream.write(data[total_written..].as_ref()) {
-                    Ok(bytes_written) if bytes_written == 0 => {
-                        error!("Connection closed while writing to socket");
-                        self.remote().shutdown();
-                        return;
-                    }
-                    Ok(bytes_written) => {
-                        total_written += bytes_written;
-                        if total_written == data.len() {
-                            break;
-                        }
-                    }
-                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                        trace!("Socket would block on write");
-                        break;
-                    }
-                    Err(e) => {
-                        error!("Failed to write to socket: {}", e);
-                        self.remote().shutdown();
-                        return;
-                    }
+        while !self.output.is_empty() {
+            match self.output.write_to(&mut self.stream) {
+                Ok(0) => {
+                    error!("Connection closed while writing to socket");
+                    self.remote().force_close();
+                    return;
+                }
+                Ok(bytes_written) => total_written += bytes_written,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    trace!("Socket would block on write");
+                    break;
+                }
+                Err(e) => {
+                    error!("Failed to write to socket: {}", e);
+                    self.remote().force_close();
+                    return;
                 }
             }
         }
-        self.output_buffer.retrieve(total_written);
-        if total_written > 0
-            && self.output_buffer.readable_bytes() == 0
-            && self.interest.is_writable()
-        {
+        if total_written > 0 {
+            self.last_active = Instant::now();
+        }
+        if total_written > 0 && self.output.is_empty() && self.interest.is_writable() {
+            // 立即去掉 WRITABLE，之后到达的写入直接写 socket，而不是排在永远不会到来的可写事件之后
             trace!("No more data to write, removing writable interest");
-            self.remote().reregister(
+            self.update_interest(
                 self.interest
-                    .remove(mio::Interest::WRITABLE)
-                    .unwrap_or(Interest::READABLE),
+                    .remove(Interest::WRITABLE)
+                    .unwrap_or(PAUSED_INTEREST),
             );
+            self.handle_write_complete();
+            if self.disconnecting && self.peer_closed {
+                self.remote().force_close();
+            } else if self.disconnecting {
+                self.shutdown_write();
+            }
+        }
+    }
+
+    fn handle_connect(&mut self) {
+        match connector::check_connected(&self.stream) {
+            Ok(true) => {
+                let connecting = self.connecting.take().unwrap();
+                if connecting.timeout.is_some() {
+                    self.signal_sender
+                        .send(SocketSignal::CancelTimer(connecting.timer));
+                }
+                trace!("Connected to {}", connecting.peer_addr);
+                self.update_interest(if self.reading {
+                    Interest::READABLE
+                } else {
+                    PAUSED_INTEREST
+                });
+                self.handle_establish(true);
+            }
+            Ok(false) => trace!("Connection is still in progress"),
+            Err(e) => self.handle_connect_error(e),
+        }
+    }
+
+    fn handle_connect_error(&mut self, error: std::io::Error) {
+        let Some(connecting) = self.connecting.take() else {
+            return;
+        };
+        if connecting.timeout.is_some() {
+            self.signal_sender
+                .send(SocketSignal::CancelTimer(connecting.timer));
+        }
+        (connecting.error_callback)(connecting.peer_addr, error);
+        self.remote().force_close();
+    }
+
+    fn arm_connect_timer(&self, connecting: &Connecting) {
+        let Some(timeout) = connecting.timeout else {
+            return;
+        };
+        let remote = self.remote().clone();
+        self.signal_sender.send(SocketSignal::AddTimer(
+            connecting.timer,
+            Instant::now() + timeout,
+            TimerCallback::Once(Box::new(move || {
+                remote.run_in_loop(|conn| {
+                    conn.handle_connect_error(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "connect timed out",
+                    ))
+                })
+            })),
+        ));
+    }
+
+    // 每个连接只有一个定时器，复用同一个 TimerId。
+    // 读写时只更新 last_active，定时器到期时再按 last_active 重新计算，每个事件的开销为 O(1)
+    fn arm_idle_timer(&self, when: Instant) {
+        let remote = self.remote().clone();
+        self.signal_sender.send(SocketSignal::AddTimer(
+            self.idle_timer,
+            when,
+            TimerCallback::Once(Box::new(move || {
+                remote.run_in_loop(|conn| conn.handle_idle_timeout())
+            })),
+        ));
+    }
+
+    fn handle_idle_timeout(&mut self) {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return;
+        };
+        let now = Instant::now();
+        let deadline = self.last_active + idle_timeout;
+        if now < deadline {
+            self.arm_idle_timer(deadline);
+            return;
+        }
+        match &self.idle_callback {
+            // 正在关闭的连接不再发送心跳，直接关闭
+            Some(callback) if !self.disconnecting => {
+                callback(self.remote().clone());
+                self.arm_idle_timer(now + idle_timeout);
+            }
+            _ => {
+                debug!(
+                    "Connection {} idle for {:?}, close it",
+                    self.remote().peer_addr(),
+                    now - self.last_active
+                );
+                self.remote().force_close();
+            }
+        }
+    }
+
+    fn schedule_shrink(&mut self) {
+        let Some(delay) = self.shrink_delay else {
+            return;
+        };
+        if self.shrink_timer_armed {
+            return;
+        }
+        self.shrink_timer_armed = true;
+        let remote = self.remote().clone();
+        self.signal_sender.send(SocketSignal::AddTimer(
+            self.shrink_timer,
+            self.last_active + delay,
+            TimerCallback::Once(Box::new(move || {
+                remote.run_in_loop(|conn| conn.handle_shrink_timeout())
+            })),
+        ));
+    }
+
+    // 与空闲定时器相同，到期时按 last_active 判断是否需要推迟
+    fn handle_shrink_timeout(&mut self) {
+        self.shrink_timer_armed = false;
+        let Some(delay) = self.shrink_delay else {
+            return;
+        };
+        if Instant::now() < self.last_active + delay {
+            self.schedule_shrink();
+            return;
+        }
+        trace!(
+            "Shrink input buffer of {:?} from {} bytes",
+            self.poll_token,
+            self.input_buffer.capacity()
+        );
+        self.input_buffer.shrink_to(buffer::INITIAL_SIZE);
+        if self.output.is_empty() {
+            self.output.shrink_to_fit();
+        }
+    }
+
+    // 半关闭写端，之后等待对端关闭（读到 0 字节）再释放连接
+    fn shutdown_write(&mut self) {
+        trace!("Shutdown write half of {:?}", self.poll_token);
+        if let Err(e) = self.stream.shutdown(Shutdown::Write) {
+            warn!("Failed to shutdown write: {}, close it", e);
+            self.remote().force_close();
         }
     }
 }
@@ -139,7 +475,35 @@ impl TcpConnection {
 impl ReactorSocket for TcpConnection {
     type Socket = TcpStream;
 
-    fn handle_event(&mut self, event: &mio::event::Event, receive_time: std::time::Instant) {
+    fn handle_event(&mut self, event: &mio::event::Event, receive_time: Instant) {
+        self.signal_sender.assert_in_loop_thread();
+        if self.connecting.is_some() {
+            self.handle_connect();
+            return;
+        }
+        if !self.reading && event.is_error() {
+            // 暂停读取期间对端重置了连接
+            let error = self.stream.take_error().ok().flatten();
+            warn!(
+                "Connection {} reset while reading is paused: {:?}",
+                self.remote().peer_addr(),
+                error
+            );
+            self.remote().force_close();
+            return;
+        }
+        // 暂停读取期间对端关闭，写端也已关闭或已半关闭且发送完毕时连接不会再有读写
+        if !self.reading
+            && event.is_read_closed()
+            && (event.is_write_closed() || (self.disconnecting && self.output.is_empty()))
+        {
+            debug!(
+                "Connection {} closed by peer while reading is paused",
+                self.remote().peer_addr()
+            );
+            self.remote().force_close();
+            return;
+        }
         if event.is_readable() {
             self.handle_read(receive_time);
         }
@@ -161,17 +525,68 @@ impl ReactorSocket for TcpConnection {
     }
 
     fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
-        self.stream.write(data)
+        let result = self.stream.write(data);
+        if matches!(result, Ok(n) if n > 0) {
+            self.last_active = Instant::now();
+        }
+        result
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        let result = self.stream.write_vectored(bufs);
+        if matches!(result, Ok(n) if n > 0) {
+            self.last_active = Instant::now();
+        }
+        result
     }
 
     fn stash_output(&mut self, data: &[u8]) {
-        self.output_buffer.append(data);
+        self.stash_chunk(Bytes::copy_from_slice(data));
+    }
+
+    fn stash_chunk(&mut self, chunk: Bytes) {
+        self.push_output(|output| output.push(chunk));
+    }
+
+    fn handle_write_complete(&mut self) {
+        if let Some(callback) = &self.write_complete_callback {
+            callback(self.remote().clone());
+        }
     }
 
     fn handle_establish(&self, is_established: bool) {
+        if let Some(connecting) = &self.connecting {
+            // 连接完成后 handle_connect 会再次调用
+            if is_established {
+                self.arm_connect_timer(connecting);
+            } else if connecting.timeout.is_some() {
+                self.signal_sender
+                    .send(SocketSignal::CancelTimer(connecting.timer));
+            }
+            return;
+        }
+        if !is_established && !self.is_established() {
+            // 连接失败，没有建立过
+            return;
+        }
         self.is_established
             .store(is_established, std::sync::atomic::Ordering::Relaxed);
         (self.connection_callback)(self.remote().clone(), is_established);
+        if let Some(idle_timeout) = self.idle_timeout {
+            if is_established {
+                self.arm_idle_timer(Instant::now() + idle_timeout);
+            } else {
+                self.signal_sender
+                    .send(SocketSignal::CancelTimer(self.idle_timer));
+            }
+        }
+        if !is_established && self.shrink_timer_armed {
+            self.signal_sender
+                .send(SocketSignal::CancelTimer(self.shrink_timer));
+        }
+        if !is_established {
+            self.remote().clear_context();
+        }
     }
 
     fn poll_token(&self) -> Option<mio::Token> {
@@ -179,10 +594,14 @@ impl ReactorSocket for TcpConnection {
     }
 
     fn set_poll_token(&mut self, token: mio::Token) {
+        // 对端在注册前就断开时取不到地址，此时连接很快会在读事件中被关闭。
+        // 正在连接时还取不到对端地址，使用连接的目标地址
+        let connecting_addr = self.connecting.as_ref().map(|c| c.peer_addr);
+        let unspecified = || connecting_addr.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
         self.poll_token = Some(token);
         self.remote = Some(Arc::new(SocketRemote::new(
-            self.stream.local_addr().unwrap(),
-            self.stream.peer_addr().unwrap(),
+            self.stream.local_addr().unwrap_or_else(|_| unspecified()),
+            self.stream.peer_addr().unwrap_or_else(|_| unspecified()),
             token,
             self.signal_sender.clone(),
             self.is_established.clone(),
@@ -198,4 +617,442 @@ impl ReactorSocket for TcpConnection {
         self.is_established
             .load(std::sync::atomic::Ordering::Relaxed)
     }
+
+    fn handle_shutdown(&mut self) -> bool {
+        // 对端已经关闭且没有待发送的数据时直接关闭
+        if self.peer_closed && self.output.is_empty() {
+            return true;
+        }
+        if !self.disconnecting {
+            self.disconnecting = true;
+            // 还有数据未发送时，由 handle_write 在发送完毕后半关闭
+            if self.output.is_empty() {
+                self.shutdown_write();
+            }
+        }
+        false
+    }
+
+    fn is_disconnecting(&self) -> bool {
+        self.disconnecting
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+            mpsc,
+        },
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+
+    use crate::{
+        EventLoopThread, TcpConnection,
+        callbacks::{ConnectionCallback, MessageCallback},
+    };
+
+    // 在后台线程中运行的 reactor，drop 时退出
+    struct RunningLoop(Option<EventLoopThread<TcpConnection>>);
+
+    impl Drop for RunningLoop {
+        fn drop(&mut self) {
+            if let Some(event_loop_thread) = self.0.take() {
+                event_loop_thread.quit();
+                // 测试失败时 reactor 线程可能也已 panic，不再等待
+                if !std::thread::panicking() {
+                    event_loop_thread.wait();
+                }
+            }
+        }
+    }
+
+    // 建立一对 TCP 连接，本端经 configure 设置后注册到新的 reactor，返回对端
+    fn connected_pair(
+        connection_callback: ConnectionCallback,
+        message_callback: MessageCallback,
+        configure: impl FnOnce(&mut TcpConnection),
+    ) -> (std::net::TcpStream, RunningLoop) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = mio::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (peer, _) = listener.accept().unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut event_loop_thread = EventLoopThread::<TcpConnection>::new(2).unwrap();
+        let remote = event_loop_thread.get_remote();
+        event_loop_thread.run();
+        let mut connection = TcpConnection::new(
+            stream,
+            connection_callback,
+            message_callback,
+            mio::Interest::READABLE,
+            remote.get_sender(),
+        );
+        configure(&mut connection);
+        remote.register(connection);
+        (peer, RunningLoop(Some(event_loop_thread)))
+    }
+
+    #[test]
+    fn test_shutdown_flushes_output() {
+        let payload = vec![b'x'; 4 * 1024 * 1024];
+        let data = payload.clone();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn.write(&data);
+                    conn.shutdown();
+                }
+            }),
+            Arc::new(|_, _, _| {}),
+            |_| {},
+        );
+
+        let mut received = Vec::new();
+        peer.read_to_end(&mut received).unwrap();
+        assert_eq!(received.len(), payload.len());
+    }
+
+    #[test]
+    fn test_peer_eof_flushes_output() {
+        let payload: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let data = payload.clone();
+        let (tx, rx) = mpsc::channel();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn.write(&data);
+                }
+                tx.send(is_connected).unwrap();
+            }),
+            Arc::new(|_, _, _| {}),
+            |_| {},
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+
+        // 对端半关闭时还有数据未发送，发送完之后才关闭连接
+        peer.shutdown(std::net::Shutdown::Write).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let mut received = Vec::new();
+        peer.read_to_end(&mut received).unwrap();
+        assert!(received == payload);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+    }
+
+    #[test]
+    fn test_write_bytes_and_vectored() {
+        let body: Bytes = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut expected = b"header".to_vec();
+        expected.extend_from_slice(&body);
+        expected.extend_from_slice(b"trailerslicetail");
+
+        let (tx, rx) = mpsc::channel();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    // 大块数据未写完时，后续的写入排在它后面
+                    assert!(conn.write_vectored(vec![
+                        Bytes::from_static(b"header"),
+                        body.clone(),
+                        Bytes::new(),
+                        Bytes::from_static(b"trailer"),
+                    ]));
+                    assert!(conn.write(b"slice"));
+                    assert!(conn.write_bytes(Bytes::from_static(b"tail")));
+                }
+            }),
+            Arc::new(|_, _, _| {}),
+            |conn| conn.set_write_complete_callback(Arc::new(move |_| tx.send(()).unwrap())),
+        );
+
+        let mut received = vec![0; expected.len()];
+        peer.read_exact(&mut received).unwrap();
+        assert!(received == expected);
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writes_keep_order() {
+        let (conn_tx, conn_rx) = mpsc::channel();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn_tx.send(conn).unwrap();
+                }
+            }),
+            Arc::new(|_, _, _| {}),
+            |_| {},
+        );
+        let conn = conn_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        // 对端间歇地停止读取使输出积压，恢复读取后输出队列在写入仍在到达时清空
+        const CHUNKS: u32 = 20_000;
+        const CHUNK_LEN: usize = 8 * 1024;
+        let writer = std::thread::spawn(move || {
+            for i in 0..CHUNKS {
+                let chunk: Vec<u8> = i.to_le_bytes().repeat(CHUNK_LEN / 4);
+                assert!(conn.write(&chunk));
+                if i % 8 == 0 {
+                    std::thread::sleep(Duration::from_micros(100));
+                }
+            }
+        });
+
+        let mut chunk = vec![0; CHUNK_LEN];
+        for i in 0..CHUNKS {
+            peer.read_exact(&mut chunk).unwrap();
+            let expected = i.to_le_bytes().repeat(CHUNK_LEN / 4);
+            assert!(chunk == expected, "chunk {} out of order", i);
+            if i % 1024 == 0 {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_send_file_interleaved() {
+        let content: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 253) as u8).collect();
+        let path = std::env::temp_dir().join(format!("send_file_{}", std::process::id()));
+        std::fs::write(&path, &content).unwrap();
+        let mut expected = b"head".to_vec();
+        expected.extend_from_slice(&content[100..]);
+        expected.extend_from_slice(b"middle");
+        expected.extend_from_slice(&content[..10]);
+        expected.extend_from_slice(b"tail");
+
+        let (tx, rx) = mpsc::channel();
+        let file_path = path.clone();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    assert!(conn.write(b"head"));
+                    conn.send_file_path(&file_path, 100, None).unwrap();
+                    assert!(conn.write(b"middle"));
+                    let file = std::fs::File::open(&file_path).unwrap();
+                    conn.send_file(file, 0, Some(10)).unwrap();
+                    assert!(conn.write_bytes(Bytes::from_static(b"tail")));
+                    let err = conn.send_file_path(&file_path, 1, Some(u64::MAX));
+                    assert!(err.is_err());
+                }
+            }),
+            Arc::new(|_, _, _| {}),
+            |conn| conn.set_write_complete_callback(Arc::new(move |_| tx.send(()).unwrap())),
+        );
+
+        let mut received = vec![0; expected.len()];
+        peer.read_exact(&mut received).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(received == expected);
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn test_write_complete_and_high_water_mark() {
+        let payload_len = 4 * 1024 * 1024;
+        let (tx, rx) = mpsc::channel();
+        let high_water_tx = tx.clone();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn.write(&vec![b'x'; payload_len]);
+                }
+            }),
+            Arc::new(|_, _, _| {}),
+            |conn| {
+                conn.set_high_water_mark_callback(
+                    Arc::new(move |_, buffered| high_water_tx.send(Some(buffered)).unwrap()),
+                    1024,
+                );
+                conn.set_write_complete_callback(Arc::new(move |_| tx.send(None).unwrap()));
+            },
+        );
+
+        let buffered = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(buffered.is_some_and(|len| len >= 1024 && len <= payload_len));
+
+        let mut received = vec![0; payload_len];
+        peer.read_exact(&mut received).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_shrink_input_buffer_after_idle() {
+        // 消息回调等数据全部到达后才取走，输入缓冲区会变大
+        let payload_len = 1024 * 1024;
+        let (conn_tx, conn_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn_tx.send(conn).unwrap();
+                }
+            }),
+            Arc::new(move |_, buffer, _| {
+                if buffer.readable_bytes() == payload_len {
+                    buffer.retrieve_all();
+                    tx.send(buffer.capacity()).unwrap();
+                }
+            }),
+            |conn| conn.set_buffer_shrink_delay(Some(Duration::from_millis(50))),
+        );
+        let conn = conn_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        peer.write_all(&vec![b'x'; payload_len]).unwrap();
+        let capacity = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(capacity >= payload_len);
+
+        std::thread::sleep(Duration::from_millis(200));
+        let (tx, rx) = mpsc::channel();
+        conn.run_in_loop(move |conn| tx.send(conn.input_buffer.capacity()).unwrap());
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Ok(crate::buffer::INITIAL_SIZE)
+        );
+    }
+
+    #[test]
+    fn test_pause_reading_at_max_input_buffer() {
+        let consume = Arc::new(AtomicBool::new(false));
+        let should_consume = consume.clone();
+        let (conn_tx, conn_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn_tx.send(conn).unwrap();
+                }
+            }),
+            Arc::new(move |_, buffer, _| {
+                tx.send(buffer.readable_bytes()).unwrap();
+                if should_consume.load(Ordering::Relaxed) {
+                    buffer.retrieve_all();
+                }
+            }),
+            |conn| conn.set_max_input_buffer(16),
+        );
+        let conn = conn_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        peer.write_all(&[b'x'; 64]).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(64));
+
+        // 暂停期间新到达的数据不会被读取
+        peer.write_all(&[b'y'; 64]).unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        consume.store(true, Ordering::Relaxed);
+        conn.start_read();
+        let mut total = 0;
+        while total < 128 {
+            total += rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+        assert_eq!(total, 128);
+    }
+
+    #[test]
+    fn test_reset_while_paused() {
+        let (tx, rx) = mpsc::channel();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn.stop_read();
+                }
+                tx.send(is_connected).unwrap();
+            }),
+            Arc::new(|_, _, _| panic!("reading is paused")),
+            |_| {},
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+
+        // 暂停期间不产生读事件，但对端重置连接时仍会关闭
+        peer.write_all(b"ignored").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        socket2::SockRef::from(&peer)
+            .set_linger(Some(Duration::ZERO))
+            .unwrap();
+        drop(peer);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+    }
+
+    #[test]
+    fn test_peer_close_while_paused() {
+        let (tx, rx) = mpsc::channel();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |conn, is_connected| {
+                if is_connected {
+                    conn.stop_read();
+                    conn.shutdown();
+                }
+                tx.send(is_connected).unwrap();
+            }),
+            Arc::new(|_, _, _| panic!("reading is paused")),
+            |_| {},
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+
+        // 已经半关闭写端，对端关闭后连接不会再有读写，暂停期间也要关闭
+        let mut received = Vec::new();
+        peer.read_to_end(&mut received).unwrap();
+        assert!(received.is_empty());
+        drop(peer);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let (tx, rx) = mpsc::channel();
+        let idle_timeout = Duration::from_millis(100);
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |_, is_connected| tx.send(is_connected).unwrap()),
+            Arc::new(|_, buffer, _| {
+                buffer.retrieve_all();
+            }),
+            |conn| conn.set_idle_timeout(idle_timeout),
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+        let start = std::time::Instant::now();
+
+        // 有数据到达时推迟关闭
+        std::thread::sleep(idle_timeout / 2);
+        peer.write_all(b"ping").unwrap();
+        let active = std::time::Instant::now();
+
+        let mut received = Vec::new();
+        peer.read_to_end(&mut received).unwrap();
+        assert!(received.is_empty());
+        assert!(active.elapsed() >= idle_timeout);
+        assert!(start.elapsed() >= idle_timeout * 3 / 2);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(false));
+    }
+
+    #[test]
+    fn test_idle_callback_heartbeat() {
+        let closed = Arc::new(AtomicBool::new(false));
+        let is_closed = closed.clone();
+        let (mut peer, _event_loop) = connected_pair(
+            Arc::new(move |_, is_connected| {
+                if !is_connected {
+                    is_closed.store(true, Ordering::Relaxed);
+                }
+            }),
+            Arc::new(|_, _, _| {}),
+            |conn| {
+                conn.set_idle_timeout(Duration::from_millis(20));
+                conn.set_idle_callback(Arc::new(|conn| {
+                    conn.write(b"hb");
+                }));
+            },
+        );
+
+        // 空闲时发送心跳而不是关闭连接
+        let mut heartbeats = [0; 6];
+        peer.read_exact(&mut heartbeats).unwrap();
+        assert_eq!(&heartbeats, b"hbhbhb");
+        assert!(!closed.load(Ordering::Relaxed));
+    }
 }
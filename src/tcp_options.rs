@@ -0,0 +1,157 @@
+use std::{io, os::fd::AsFd, time::Duration};
+
+use socket2::{SockRef, TcpKeepalive};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    // 连接空闲多久后开始发送探测
+    pub idle: Duration,
+    pub interval: Option<Duration>,
+    pub count: Option<u32>,
+}
+
+impl Keepalive {
+    pub fn new(idle: Duration) -> Self {
+        Keepalive {
+            idle,
+            interval: None,
+            count: None,
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    pub fn count(mut self, count: u32) -> Self {
+        self.count = Some(count);
+        self
+    }
+}
+
+// 未设置的选项保持系统默认值
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TcpOptions {
+    nodelay: Option<bool>,
+    keepalive: Option<Option<Keepalive>>,
+    linger: Option<Option<Duration>>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+}
+
+impl TcpOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // TCP_NODELAY，关闭 Nagle 算法
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    // SO_KEEPALIVE，None 表示关闭
+    pub fn keepalive(mut self, keepalive: Option<Keepalive>) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    // SO_LINGER，None 表示关闭
+    pub fn linger(mut self, linger: Option<Duration>) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    // SO_SNDBUF，内核可能会调整实际大小
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    // SO_RCVBUF，内核可能会调整实际大小
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    pub fn apply<S: AsFd>(&self, socket: &S) -> io::Result<()> {
+        let socket = SockRef::from(socket);
+        if let Some(nodelay) = self.nodelay {
+            socket.set_tcp_nodelay(nodelay)?;
+        }
+        match self.keepalive {
+            Some(Some(keepalive)) => {
+                let mut params = TcpKeepalive::new().with_time(keepalive.idle);
+                if let Some(interval) = keepalive.interval {
+                    params = params.with_interval(interval);
+                }
+                if let Some(count) = keepalive.count {
+                    params = params.with_retries(count);
+                }
+                socket.set_tcp_keepalive(&params)?;
+            }
+            Some(None) => socket.set_keepalive(false)?,
+            None => {}
+        }
+        if let Some(linger) = self.linger {
+            socket.set_linger(linger)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use socket2::SockRef;
+
+    use super::{Keepalive, TcpOptions};
+
+    #[test]
+    fn test_apply() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        TcpOptions::new()
+            .nodelay(true)
+            .keepalive(Some(
+                Keepalive::new(Duration::from_secs(30))
+                    .interval(Duration::from_secs(5))
+                    .count(3),
+            ))
+            .linger(Some(Duration::from_secs(1)))
+            .send_buffer_size(64 * 1024)
+            .recv_buffer_size(64 * 1024)
+            .apply(&stream)
+            .unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(socket.tcp_nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        assert_eq!(
+            socket.tcp_keepalive_time().unwrap(),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            socket.tcp_keepalive_interval().unwrap(),
+            Duration::from_secs(5)
+        );
+        assert_eq!(socket.tcp_keepalive_retries().unwrap(), 3);
+        assert_eq!(socket.linger().unwrap(), Some(Duration::from_secs(1)));
+        assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+
+        // 未设置的选项保持不变
+        TcpOptions::new().keepalive(None).apply(&stream).unwrap();
+        assert!(!socket.keepalive().unwrap());
+        assert!(socket.tcp_nodelay().unwrap());
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use log::trace;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerId(u64);
+
+impl TimerId {
+    // 全局递增，保证在其他线程中也能提前拿到 id
+    pub fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        TimerId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+pub enum TimerCallback {
+    Once(Box<dyn FnOnce() + Send>),
+    Repeat(Box<dyn FnMut() + Send>, Duration),
+}
+
+pub struct TimerQueue {
+    deadlines: BinaryHeap<Reverse<(Instant, TimerId)>>,
+    timers: HashMap<TimerId, TimerCallback>,
+}
+
+impl TimerQueue {
+    pub fn new() -> Self {
+        TimerQueue {
+            deadlines: BinaryHeap::new(),
+            timers: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, id: TimerId, when: Instant, callback: TimerCallback) {
+        self.timers.insert(id, callback);
+        self.deadlines.push(Reverse((when, id)));
+    }
+
+    // 只从表中移除，堆中的过期项在弹出时跳过
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        self.timers.remove(&id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    // 距离下一个定时器到期的时间，没有定时器时返回 None（无限等待）
+    pub fn next_timeout(&mut self, now: Instant) -> Option<Duration> {
+        self.discard_cancelled();
+        self.deadlines
+            .peek()
+            .map(|Reverse((when, _))| when.saturating_duration_since(now))
+    }
+
+    pub fn run_expired(&mut self, now: Instant) {
+        let mut repeats = Vec::new();
+        while let Some(Reverse((when, id))) = self.deadlines.peek().copied() {
+            if when > now {
+                break;
+            }
+            self.deadlines.pop();
+            let Some(callback) = self.timers.remove(&id) else {
+                continue;
+            };
+            trace!("timer {:?} expired", id);
+            match callback {
+                TimerCallback::Once(callback) => callback(),
+                TimerCallback::Repeat(mut callback, interval) => {
+                    callback();
+                    repeats.push((id, interval, callback));
+                }
+            }
+        }
+        // 周期定时器在本轮结束后重新加入，避免 interval 为 0 时死循环
+        for (id, interval, callback) in repeats {
+            self.add(
+                id,
+                now + interval,
+                TimerCallback::Repeat(callback, interval),
+            );
+        }
+    }
+
+    fn discard_cancelled(&mut self) {
+        while let Some(Reverse((_, id))) = self.deadlines.peek() {
+            if self.timers.contains_key(id) {
+                break;
+            }
+            self.deadlines.pop();
+        }
+    }
+}
+
+impl Default for TimerQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_run_in_deadline_order() {
+        let mut queue = TimerQueue::new();
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let now = Instant::now();
+
+        for (name, delay) in [("b", 20), ("a", 10), ("c", 30)] {
+            let fired = fired.clone();
+            queue.add(
+                TimerId::next(),
+                now + Duration::from_millis(delay),
+                TimerCallback::Once(Box::new(move || fired.lock().unwrap().push(name))),
+            );
+        }
+        assert_eq!(queue.next_timeout(now), Some(Duration::from_millis(10)));
+
+        queue.run_expired(now + Duration::from_millis(20));
+        assert_eq!(*fired.lock().unwrap(), vec!["a", "b"]);
+        assert_eq!(queue.len(), 1);
+
+        queue.run_expired(now + Duration::from_millis(30));
+        assert_eq!(*fired.lock().unwrap(), vec!["a", "b", "c"]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.next_timeout(now), None);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut queue = TimerQueue::new();
+        let fired = Arc::new(Mutex::new(false));
+        let now = Instant::now();
+        let id = TimerId::next();
+        let flag = fired.clone();
+        queue.add(
+            id,
+            now,
+            TimerCallback::Once(Box::new(move || *flag.lock().unwrap() = true)),
+        );
+
+        assert!(queue.cancel(id));
+        assert!(!queue.cancel(id));
+        assert_eq!(queue.next_timeout(now), None);
+        queue.run_expired(now);
+        assert!(!*fired.lock().unwrap());
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut queue = TimerQueue::new();
+        let count = Arc::new(Mutex::new(0));
+        let now = Instant::now();
+        let interval = Duration::from_millis(5);
+        let id = TimerId::next();
+        let counter = count.clone();
+        queue.add(
+            id,
+            now + interval,
+            TimerCallback::Repeat(Box::new(move || *counter.lock().unwrap() += 1), interval),
+        );
+
+        queue.run_expired(now + interval);
+        queue.run_expired(now + interval * 2);
+        assert_eq!(*count.lock().unwrap(), 2);
+        assert_eq!(queue.next_timeout(now + interval * 2), Some(interval));
+
+        queue.cancel(id);
+        queue.run_expired(now + interval * 10);
+        assert_eq!(*count.lock().unwrap(), 2);
+    }
+}
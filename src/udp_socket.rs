@@ -6,15 +6,14 @@ use std::{
 use log::{error, info};
 use mio::Interest;
 
-use crate::{
-    SocketRemote, callbacks::DatagramCallback, reactor::ReactorSignal, reactor_channel::Sender,
-};
+use crate::{SocketRemote, callbacks::DatagramCallback, socket_sender::SocketSender};
 
 pub struct UdpSocket {
     socket: mio::net::UdpSocket,
-    buffer: [u8; 65536],
+    // 放在堆上，避免 socket 本身过大
+    buffer: Box<[u8]>,
     datagram_callback: DatagramCallback,
-    signal_sender: Sender<ReactorSignal<Self>>,
+    signal_sender: SocketSender<Self>,
     remote: Option<Arc<SocketRemote<Self>>>,
     poll_token: Option<mio::Token>,
     pub is_established: Arc<AtomicBool>,
@@ -24,13 +23,13 @@ impl UdpSocket {
     pub fn new(
         socket: mio::net::UdpSocket,
         datagram_callback: DatagramCallback,
-        signal_sender: Sender<ReactorSignal<Self>>,
+        signal_sender: impl Into<SocketSender<Self>>,
     ) -> Self {
         UdpSocket {
             socket,
-            buffer: [0; 65536],
+            buffer: vec![0; 65536].into_boxed_slice(),
             datagram_callback,
-            signal_sender,
+            signal_sender: signal_sender.into(),
             remote: None,
             poll_token: None,
             is_established: Arc::new(AtomicBool::new(false)),
@@ -55,9 +54,13 @@ impl crate::ReactorSocket for UdpSocket {
         );
         self.is_established
             .store(is_established, std::sync::atomic::Ordering::Relaxed);
+        if !is_established {
+            self.remote().clear_context();
+        }
     }
 
     fn handle_event(&mut self, event: &mio::event::Event, receive_time: std::time::Instant) {
+        self.signal_sender.assert_in_loop_thread();
         if event.is_readable() {
             loop {
                 match self.socket.recv_from(&mut self.buffer) {